@@ -0,0 +1,40 @@
+// Parsing a 1000-friend sheet and folding it into the friend list, as a run
+// does before fetching any feeds. `cargo bench --bench sheet_parse`
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use letterboxd_bot::sheet::{UTF8_BOM, parse_sheet, unique_friends, without_bom};
+use std::hint::black_box;
+
+const FRIENDS: usize = 1000;
+
+/// A Sheets-style export: BOM, header, a flair column, a few quoted names and
+/// some people listed twice with different spacing, as merged circles are.
+fn synthetic_sheet() -> Vec<u8> {
+    let mut csv = UTF8_BOM.to_vec();
+    csv.extend_from_slice("Name,Username,Flair\n".as_bytes());
+    for i in 0..FRIENDS {
+        let row = match i % 10 {
+            0 => format!("\"Smith, Friend {i}\",friend{i},🇬🇧\n"),
+            1 => format!("Friend  {},friend{},\n", i - 1, i - 1),
+            _ => format!("Friend {i},friend{i},✨\n"),
+        };
+        csv.extend_from_slice(row.as_bytes());
+    }
+    csv
+}
+
+fn parse_and_build(c: &mut Criterion) {
+    let csv = synthetic_sheet();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    c.bench_function("parse_sheet + unique_friends, 1000 rows", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let reader = without_bom(black_box(&csv[..])).await.unwrap();
+            let friends = parse_sheet(reader).await.unwrap();
+            black_box(unique_friends(friends))
+        })
+    });
+}
+
+criterion_group!(benches, parse_and_build);
+criterion_main!(benches);
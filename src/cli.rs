@@ -0,0 +1,56 @@
+use crate::recap::RecapPeriod;
+use anyhow::{Result, bail};
+
+/// Command-line flags. Everything else is configured through env vars.
+#[derive(Default)]
+pub struct Cli {
+    /// Print a line per feed item saying why it was included or dropped
+    pub explain: bool,
+    /// Tally the reactions on this message and post the winner, instead of a digest
+    pub tally: Option<String>,
+    /// Print the digest as Markdown and exit without sending anything
+    pub stdout_markdown: bool,
+    /// Check the sheet, every feed and the Whapi token, then exit without sending
+    pub validate: bool,
+    /// Build the digest and print it, with each review's score, instead of sending
+    pub dry_run: bool,
+    /// Name the friends whose feeds couldn't be read at the end of the digest
+    pub fail_report: bool,
+    /// Keep running and post on SCHEDULE (weekly by default), serving health checks
+    pub daemon: bool,
+    /// Post a month- or year-to-date recap instead of the weekly digest
+    pub recap: Option<RecapPeriod>,
+    /// Read settings and friends from this TOML file (BOT_CONFIG works too)
+    pub config: Option<String>,
+}
+
+impl Cli {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut cli = Cli::default();
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--explain" => cli.explain = true,
+                "--stdout-markdown" => cli.stdout_markdown = true,
+                "--validate" => cli.validate = true,
+                "--dry-run" => cli.dry_run = true,
+                "--fail-report" => cli.fail_report = true,
+                "--daemon" => cli.daemon = true,
+                "--tally" => match args.next_if(|next| !next.starts_with("--")) {
+                    Some(message_id) => cli.tally = Some(message_id),
+                    None => bail!("--tally needs the id of the poll message"),
+                },
+                "--recap" => match args.next_if(|next| !next.starts_with("--")) {
+                    Some(period) => cli.recap = Some(period.parse()?),
+                    None => bail!("--recap needs a period, monthly or yearly"),
+                },
+                "--config" => match args.next_if(|next| !next.starts_with("--")) {
+                    Some(path) => cli.config = Some(path),
+                    None => bail!("--config needs the path of a config file"),
+                },
+                other => bail!("Unknown argument '{}'", other),
+            }
+        }
+        Ok(cli)
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbImage};
+use reqwest::Client;
+use std::io::Cursor;
+use tracing::warn;
+
+const POSTER_BASE: &str = "https://image.tmdb.org/t/p/w185";
+// Posters are 2:3; every tile is scaled to this so the grid lines up
+const TILE_WIDTH: u32 = 185;
+const TILE_HEIGHT: u32 = 278;
+// Past this the tiles get too small to recognise on a phone
+const MAX_POSTERS: usize = 12;
+const JPEG_QUALITY: u8 = 85;
+
+/// Downloads the posters and tiles them into a near-square grid, returned
+/// as JPEG bytes. Posters that fail to download are left out; it's only an
+/// error if none of them can be used.
+pub async fn build_collage(client: &Client, poster_paths: &[&str]) -> Result<Vec<u8>> {
+    let mut tiles = Vec::new();
+    for path in poster_paths.iter().take(MAX_POSTERS) {
+        match fetch_poster(client, path).await {
+            Ok(poster) => {
+                tiles.push(poster.resize_exact(TILE_WIDTH, TILE_HEIGHT, FilterType::Triangle))
+            }
+            Err(e) => warn!(poster = %path, error = %e, "Skipping poster"),
+        }
+    }
+    if tiles.is_empty() {
+        anyhow::bail!("No posters could be fetched");
+    }
+
+    let columns = (tiles.len() as f64).sqrt().ceil() as u32;
+    let rows = (tiles.len() as u32).div_ceil(columns);
+    let mut canvas = RgbImage::new(columns * TILE_WIDTH, rows * TILE_HEIGHT);
+    for (i, tile) in tiles.iter().enumerate() {
+        let (col, row) = (i as u32 % columns, i as u32 / columns);
+        image::imageops::replace(
+            &mut canvas,
+            &tile.to_rgb8(),
+            (col * TILE_WIDTH) as i64,
+            (row * TILE_HEIGHT) as i64,
+        );
+    }
+
+    let mut jpeg = Vec::new();
+    let encoder =
+        image::codecs::jpeg::JpegEncoder::new_with_quality(Cursor::new(&mut jpeg), JPEG_QUALITY);
+    DynamicImage::ImageRgb8(canvas)
+        .write_with_encoder(encoder)
+        .context("Couldn't encode the collage")?;
+    Ok(jpeg)
+}
+
+async fn fetch_poster(client: &Client, path: &str) -> Result<DynamicImage> {
+    let response = client
+        .get(format!("{}{}", POSTER_BASE, path))
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        anyhow::bail!("HTTP {}", response.status());
+    }
+    let bytes = response.bytes().await?;
+    Ok(image::load_from_memory(&bytes)?)
+}
@@ -0,0 +1,569 @@
+use crate::config_file::{ConfigFile, FriendEntry};
+use crate::emoji::{EmojiSet, EmojiTheme};
+use crate::quips::QuipPools;
+use anyhow::{Context, Result, bail};
+use chrono::{NaiveDate, Weekday};
+use chrono_tz::Tz;
+use cron::Schedule;
+use std::collections::HashMap;
+use std::env;
+use std::str::FromStr;
+
+const DEFAULT_LETTERBOXD_BASE: &str = "https://letterboxd.com";
+
+pub struct Config {
+    // SHEET_CSV_URL, comma-separated to merge several friend circles
+    pub sheet_urls: Vec<String>,
+    // The config file's [[friends]]; when there are any the sheets aren't read
+    pub friends: Vec<FriendEntry>,
+    // Where the digest is delivered; each backend needs its own credentials
+    pub messenger: MessengerKind,
+    pub whapi_token: String,
+    pub group_id: String,
+    pub telegram_bot_token: String,
+    // A numeric chat id, or @channelname for a public channel
+    pub telegram_chat_id: String,
+    pub discord_webhook_url: String,
+    // The operator's own chat, sent a one-line summary after each digest
+    pub admin_chat_id: Option<String>,
+    // Base used to build feed URLs, e.g. a mirror when letterboxd.com blocks us
+    pub letterboxd_base: String,
+    pub http_proxy: Option<String>,
+    // Timezone used for anything displayed or reasoned about in calendar terms
+    pub timezone: Tz,
+    pub week_boundary: WeekBoundary,
+    pub week_start_day: Weekday,
+    pub show_tags: bool,
+    // Lowercased, without '#'; entries carrying one are left out entirely
+    pub private_tags: Vec<String>,
+    pub layout: Layout,
+    pub sort_mode: SortMode,
+    pub include_undated: bool,
+    // Count list and other non-diary feed items as watches too
+    pub include_non_watches: bool,
+    // Group "Film: Director's Cut" and friends under the base film
+    pub merge_editions: bool,
+    pub show_reviews: bool,
+    // ❤️ on liked entries and "rewatched" for rewatches
+    pub show_diary_details: bool,
+    pub spoiler_safe: bool,
+    // Fixed seed for flavor text; defaults to one derived from the run date
+    pub rng_seed: Option<u64>,
+    pub output_pdf: bool,
+    pub skip_if_empty: bool,
+    pub anonymize: bool,
+    pub ics_output_path: Option<String>,
+    pub canonical_stars: bool,
+    pub star_style: StarStyle,
+    // Reaction per rating band, from EMOJI_THEME with EMOJI_MAP overrides
+    pub emoji: EmojiSet,
+    pub show_quips: bool,
+    // A random "Banger!"-style phrase after each 5-star rating
+    pub show_banger_phrases: bool,
+    // Quip pools per average band, with QUIP_MAP overrides
+    pub quips: QuipPools,
+    pub tmdb_api_key: Option<String>,
+    // JSON file keeping TMDB ids and details between runs
+    pub tmdb_cache_path: Option<String>,
+    pub show_providers: bool,
+    pub show_most_active: bool,
+    pub show_goals: bool,
+    // Yearly film goal per username, from GOALS_JSON like {"luke": 200}
+    pub goals: HashMap<String, u32>,
+    pub show_leaderboard: bool,
+    pub leaderboard_size: usize,
+    pub show_genre_summary: bool,
+    pub link_target: LinkTarget,
+    pub show_watchtime: bool,
+    // Release year and TMDB runtime next to each film's title
+    pub show_film_meta: bool,
+    pub show_on_this_day: bool,
+    // Also send a grid of the week's TMDB posters as an image
+    pub poster_collage: bool,
+    pub story_card: bool,
+    // ISO 3166-1 country code used for streaming availability
+    pub watch_region: String,
+    pub show_member_stats: bool,
+    pub member_stats_cache: Option<String>,
+    pub max_reviewers_per_film: Option<usize>,
+    // One "Watched by A, B, C" line instead of a bullet per unrated watch
+    pub compress_watched: bool,
+    pub max_title_len: Option<usize>,
+    pub webhook_url: Option<String>,
+    // Sent as X-Webhook-Secret so the receiver can verify the caller
+    pub webhook_secret: Option<String>,
+    // Minimum gap (plus up to `fetch_jitter_ms` random extra) between the starts
+    // of requests to Letterboxd. Costs roughly delay × friends of extra runtime,
+    // in exchange for looking less like a scraper and avoiding 429s.
+    pub fetch_delay_ms: u64,
+    pub fetch_jitter_ms: u64,
+    // How many feeds are fetched at once; the delay above still spaces their starts
+    pub fetch_concurrency: usize,
+    // Idle connections kept per host for reuse across the feed fetches
+    pub pool_max_idle: usize,
+    // A friend's feed larger than this is reported as failed, not parsed
+    pub max_feed_bytes: usize,
+    // Per attempt, from connecting to the last byte of the feed
+    pub feed_timeout_secs: u64,
+    // Extra attempts after a timeout or 5xx before a feed counts as failed
+    pub feed_retries: u32,
+    // Also set by --fail-report: say in the digest whose feeds failed
+    pub fail_report: bool,
+    // JSON file remembering what earlier runs already sent
+    pub state_path: Option<String>,
+    // Leave out entries already covered by a sent digest, so the bot can run
+    // more often than weekly; needs STATE_PATH
+    pub since_last_send: bool,
+    pub failures_json_path: Option<String>,
+    // Append-only JSONL history of every digest sent
+    pub sent_log_path: Option<String>,
+    // Through this date (in `timezone`) digests are built but not sent
+    pub quiet_until: Option<NaiveDate>,
+    // Keep serving /healthz and /readyz on `health_port` after the run
+    pub serve_health: bool,
+    pub health_port: u16,
+    // From SCHEDULE (or SCHEDULE_CRON); when set the bot stays running and
+    // posts on this schedule
+    pub schedule: Option<Schedule>,
+    pub debug: bool,
+    // How log lines are written to stdout
+    pub log_format: LogFormat,
+    // Build the digest and print it instead of sending anything
+    pub dry_run: bool,
+}
+
+/// How each film is rendered in the round-up.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layout {
+    /// Title, link and one bullet per reviewer.
+    Full,
+    /// A single summary line per film.
+    Terse,
+    /// A header message with each film sent as a reply quoting it.
+    Threaded,
+    /// An aligned leaderboard of title, average and watcher count.
+    Table,
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(Layout::Full),
+            "terse" => Ok(Layout::Terse),
+            "threaded" => Ok(Layout::Threaded),
+            "table" => Ok(Layout::Table),
+            other => bail!(
+                "Unknown LAYOUT '{}', expected full, terse, threaded or table",
+                other
+            ),
+        }
+    }
+}
+
+/// How log lines are written, from LOG_FORMAT.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LogFormat {
+    /// One human-readable line per event.
+    Text,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => bail!("Unknown LOG_FORMAT '{}', expected text or json", other),
+        }
+    }
+}
+
+/// Which chat platform the digest is sent through.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MessengerKind {
+    /// WhatsApp via Whapi: WHAPI_TOKEN and GROUP_ID.
+    Whapi,
+    /// A Telegram bot: TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID.
+    Telegram,
+    /// A Discord channel webhook: DISCORD_WEBHOOK_URL.
+    Discord,
+}
+
+impl FromStr for MessengerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "whapi" => Ok(MessengerKind::Whapi),
+            "telegram" => Ok(MessengerKind::Telegram),
+            "discord" => Ok(MessengerKind::Discord),
+            other => bail!(
+                "Unknown MESSENGER '{}', expected whapi, telegram or discord",
+                other
+            ),
+        }
+    }
+}
+
+/// How ratings are drawn.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StarStyle {
+    /// ★★★½, the plain glyphs Letterboxd uses.
+    Glyph,
+    /// ⭐⭐⭐½, which WhatsApp shows in color.
+    Emoji,
+}
+
+impl FromStr for StarStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "glyph" => Ok(StarStyle::Glyph),
+            "emoji" => Ok(StarStyle::Emoji),
+            other => bail!("Unknown STAR_STYLE '{}', expected glyph or emoji", other),
+        }
+    }
+}
+
+/// The order films appear in the round-up.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortMode {
+    /// Alphabetical by title.
+    Title,
+    /// By the earliest watch of each film, with a divider when the day changes.
+    Chronological,
+}
+
+impl FromStr for SortMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "title" => Ok(SortMode::Title),
+            "chronological" => Ok(SortMode::Chronological),
+            other => bail!(
+                "Unknown SORT_MODE '{}', expected title or chronological",
+                other
+            ),
+        }
+    }
+}
+
+/// Which site each film's title links to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LinkTarget {
+    Letterboxd,
+    /// Resolved through TMDB; films it can't resolve keep the Letterboxd link.
+    Tmdb,
+    /// Resolved through TMDB's IMDb id, with the same fallback.
+    Imdb,
+}
+
+impl FromStr for LinkTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "letterboxd" => Ok(LinkTarget::Letterboxd),
+            "tmdb" => Ok(LinkTarget::Tmdb),
+            "imdb" => Ok(LinkTarget::Imdb),
+            other => bail!(
+                "Unknown LINK_TARGET '{}', expected letterboxd, tmdb or imdb",
+                other
+            ),
+        }
+    }
+}
+
+/// How far back `get_movie_map` looks for diary entries.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WeekBoundary {
+    /// The last 7×24 hours, whatever time the bot runs.
+    Rolling,
+    /// Since the start of the calendar week in the configured timezone.
+    Calendar,
+}
+
+impl FromStr for WeekBoundary {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "rolling" => Ok(WeekBoundary::Rolling),
+            "calendar" => Ok(WeekBoundary::Calendar),
+            other => bail!(
+                "Unknown WEEK_BOUNDARY '{}', expected rolling or calendar",
+                other
+            ),
+        }
+    }
+}
+
+impl Config {
+    /// `dry_run` is the --dry-run flag; DRY_RUN turns it on too. Only the
+    /// chosen messenger's credentials are required, and a dry run never
+    /// sends anything so it needs none of them. Settings in `file` apply
+    /// wherever the matching env var isn't set.
+    pub fn from_env(dry_run: bool, file: ConfigFile) -> Result<Self> {
+        let settings = Settings {
+            read_env: true,
+            file: file.settings()?,
+        };
+        Self::from_settings(dry_run, file.friends, &settings)
+    }
+
+    /// A config from the given settings alone, ignoring the environment, so
+    /// tests get the same defaults wherever they run.
+    #[cfg(test)]
+    pub fn for_tests(pairs: &[(&str, &str)]) -> Self {
+        let mut file: HashMap<String, String> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        file.entry("SHEET_CSV_URL".to_string())
+            .or_insert_with(|| "http://localhost/sheet.csv".to_string());
+        let settings = Settings {
+            read_env: false,
+            file,
+        };
+        Self::from_settings(true, Vec::new(), &settings).expect("test settings are valid")
+    }
+
+    fn from_settings(dry_run: bool, friends: Vec<FriendEntry>, s: &Settings) -> Result<Self> {
+        let dry_run = dry_run || s.flag("DRY_RUN");
+        let messenger = s.parse("MESSENGER", MessengerKind::Whapi)?;
+        let credential = |key: &str, backend: MessengerKind| -> Result<String> {
+            match s.get(key) {
+                Some(value) => Ok(value),
+                None if dry_run || messenger != backend => Ok(String::new()),
+                None => bail!("Missing {} env var", key),
+            }
+        };
+        let sheet_urls: Vec<String> = s
+            .or("SHEET_CSV_URL", "")
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect();
+        if sheet_urls.is_empty() && friends.is_empty() {
+            bail!("Missing SHEET_CSV_URL env var (or [[friends]] in the config file)");
+        }
+        Ok(Config {
+            sheet_urls,
+            friends,
+            messenger,
+            whapi_token: credential("WHAPI_TOKEN", MessengerKind::Whapi)?,
+            group_id: credential("GROUP_ID", MessengerKind::Whapi)?,
+            telegram_bot_token: credential("TELEGRAM_BOT_TOKEN", MessengerKind::Telegram)?,
+            telegram_chat_id: credential("TELEGRAM_CHAT_ID", MessengerKind::Telegram)?,
+            discord_webhook_url: credential("DISCORD_WEBHOOK_URL", MessengerKind::Discord)?,
+            admin_chat_id: s.opt("ADMIN_CHAT_ID"),
+            letterboxd_base: s.or("LETTERBOXD_BASE", DEFAULT_LETTERBOXD_BASE),
+            http_proxy: s.opt("HTTP_PROXY"),
+            timezone: s.parse("TIMEZONE", Tz::UTC)?,
+            week_boundary: s.parse("WEEK_BOUNDARY", WeekBoundary::Rolling)?,
+            week_start_day: s.parse("WEEK_START_DAY", Weekday::Mon)?,
+            show_tags: s.flag("SHOW_TAGS"),
+            private_tags: s
+                .or("PRIVATE_TAGS", "")
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches('#').to_lowercase())
+                .filter(|tag| !tag.is_empty())
+                .collect(),
+            layout: s.parse("LAYOUT", Layout::Full)?,
+            sort_mode: s.parse("SORT_MODE", SortMode::Title)?,
+            include_undated: s.flag("INCLUDE_UNDATED"),
+            include_non_watches: s.flag("INCLUDE_NON_WATCHES"),
+            merge_editions: s.flag("MERGE_EDITIONS"),
+            show_reviews: s.flag("SHOW_REVIEWS"),
+            show_diary_details: s.flag("SHOW_DIARY_DETAILS"),
+            spoiler_safe: s.flag("SPOILER_SAFE"),
+            output_pdf: s.flag("OUTPUT_PDF"),
+            skip_if_empty: s.flag("SKIP_IF_EMPTY"),
+            anonymize: s.flag("ANONYMIZE"),
+            ics_output_path: s.opt("ICS_OUTPUT_PATH"),
+            canonical_stars: s.flag("CANONICAL_STARS"),
+            star_style: s.parse("STAR_STYLE", StarStyle::Glyph)?,
+            emoji: EmojiSet::for_theme(s.parse("EMOJI_THEME", EmojiTheme::Classic)?)
+                .with_overrides(&s.or("EMOJI_MAP", ""))?,
+            show_quips: s.flag("SHOW_QUIPS"),
+            show_banger_phrases: s.flag("SHOW_BANGER_PHRASES"),
+            quips: QuipPools::default().with_overrides(&s.or("QUIP_MAP", ""))?,
+            tmdb_api_key: s.opt("TMDB_API_KEY"),
+            tmdb_cache_path: s.opt("TMDB_CACHE_PATH"),
+            show_providers: s.flag("SHOW_PROVIDERS"),
+            show_most_active: s.flag("SHOW_MOST_ACTIVE"),
+            show_goals: s.flag("SHOW_GOALS"),
+            goals: parse_goals(&s.or("GOALS_JSON", "{}"))?,
+            show_leaderboard: s.flag("SHOW_LEADERBOARD"),
+            leaderboard_size: s.parse("LEADERBOARD_SIZE", 3)?,
+            show_genre_summary: s.flag("SHOW_GENRE_SUMMARY"),
+            link_target: s.parse("LINK_TARGET", LinkTarget::Letterboxd)?,
+            show_watchtime: s.flag("SHOW_WATCHTIME"),
+            show_film_meta: s.flag("SHOW_FILM_META"),
+            show_on_this_day: s.flag("SHOW_ON_THIS_DAY"),
+            poster_collage: s.flag("POSTER_COLLAGE"),
+            story_card: s.flag("STORY_CARD"),
+            watch_region: s.or("WATCH_REGION", "US").to_uppercase(),
+            show_member_stats: s.flag("SHOW_MEMBER_STATS"),
+            member_stats_cache: s.opt("MEMBER_STATS_CACHE"),
+            max_reviewers_per_film: s.parse_opt("MAX_REVIEWERS_PER_FILM")?,
+            compress_watched: s.flag("COMPRESS_WATCHED"),
+            max_title_len: s.parse_opt("MAX_TITLE_LEN")?,
+            webhook_url: s.opt("WEBHOOK_URL"),
+            webhook_secret: s.opt("WEBHOOK_SECRET"),
+            fetch_delay_ms: s.parse("FETCH_DELAY_MS", 0)?,
+            fetch_jitter_ms: s.parse("FETCH_JITTER_MS", 0)?,
+            fetch_concurrency: s.parse("FETCH_CONCURRENCY", 8)?,
+            pool_max_idle: s.parse("POOL_MAX_IDLE", 8)?,
+            max_feed_bytes: s.parse("MAX_FEED_BYTES", 5 * 1024 * 1024)?,
+            feed_timeout_secs: s.parse("FEED_TIMEOUT_SECS", 20)?,
+            feed_retries: s.parse("FEED_RETRIES", 2)?,
+            fail_report: s.flag("FAIL_REPORT"),
+            rng_seed: s.parse_opt("RNG_SEED")?,
+            state_path: s.opt("STATE_PATH"),
+            since_last_send: s.flag("SINCE_LAST_SEND"),
+            failures_json_path: s.opt("FAILURES_JSON_PATH"),
+            sent_log_path: s.opt("SENT_LOG_PATH"),
+            quiet_until: s.parse_opt("QUIET_UNTIL")?,
+            serve_health: s.flag("SERVE_HEALTH"),
+            health_port: s.parse("HEALTH_PORT", 8080)?,
+            schedule: s
+                .opt("SCHEDULE")
+                .or_else(|| s.opt("SCHEDULE_CRON"))
+                .map(|raw| parse_schedule(&raw))
+                .transpose()?,
+            debug: s.flag("DEBUG"),
+            log_format: s.parse("LOG_FORMAT", LogFormat::Text)?,
+            dry_run,
+        })
+    }
+}
+
+/// Usernames are matched case-insensitively, like the roster.
+fn parse_goals(raw: &str) -> Result<HashMap<String, u32>> {
+    let goals: HashMap<String, u32> = serde_json::from_str(raw)
+        .context("GOALS_JSON should be an object of username to yearly goal")?;
+    Ok(goals
+        .into_iter()
+        .map(|(username, goal)| (username.to_lowercase(), goal))
+        .collect())
+}
+
+/// Accepts the usual five-field crontab form ("0 9 * * MON") as well as the
+/// cron crate's own form with a leading seconds field.
+pub fn parse_schedule(raw: &str) -> Result<Schedule> {
+    let expression = if raw.split_whitespace().count() == 5 {
+        format!("0 {}", raw)
+    } else {
+        raw.to_string()
+    };
+    Schedule::from_str(&expression)
+        .with_context(|| format!("Invalid SCHEDULE '{}', expected a cron expression", raw))
+}
+
+// --- SETTINGS ---
+
+/// Where settings are read from: the environment first, then the config
+/// file's value under the same name.
+struct Settings {
+    read_env: bool,
+    file: HashMap<String, String>,
+}
+
+impl Settings {
+    fn get(&self, key: &str) -> Option<String> {
+        self.read_env
+            .then(|| env::var(key).ok())
+            .flatten()
+            .or_else(|| self.file.get(key).cloned())
+    }
+
+    /// Returns the setting if it is set and not blank.
+    fn opt(&self, key: &str) -> Option<String> {
+        self.get(key)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+
+    /// True for "true"/"1"/"yes" (any case), false otherwise or when unset.
+    fn flag(&self, key: &str) -> bool {
+        self.opt(key)
+            .is_some_and(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes"))
+    }
+
+    fn or(&self, key: &str, default: &str) -> String {
+        self.opt(key).unwrap_or_else(|| default.to_string())
+    }
+
+    /// Parses the setting with `FromStr`, falling back to `default` when unset.
+    fn parse<T>(&self, key: &str, default: T) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        match self.opt(key) {
+            Some(raw) => raw
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid {} '{}': {}", key, raw, e)),
+            None => Ok(default),
+        }
+    }
+
+    /// Like `parse`, but unset means `None` rather than a default.
+    fn parse_opt<T>(&self, key: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.opt(key)
+            .map(|raw| {
+                raw.parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid {} '{}': {}", key, raw, e))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_settings_are_read_under_their_env_names() {
+        let config = Config::for_tests(&[
+            ("SHOW_GOALS", "true"),
+            ("LEADERBOARD_SIZE", "5"),
+            ("QUIET_UNTIL", "2026-12-24"),
+        ]);
+        assert!(config.show_goals);
+        assert_eq!(config.leaderboard_size, 5);
+        assert_eq!(config.quiet_until, NaiveDate::from_ymd_opt(2026, 12, 24));
+        assert!(!config.show_quips);
+    }
+
+    #[test]
+    fn friends_from_the_file_stand_in_for_the_sheet() {
+        let settings = Settings {
+            read_env: false,
+            file: HashMap::new(),
+        };
+        let friends = vec![FriendEntry {
+            name: "Ann".to_string(),
+            username: "ann".to_string(),
+            flair: None,
+            alias: None,
+        }];
+        assert!(Config::from_settings(true, Vec::new(), &settings).is_err());
+        let config = Config::from_settings(true, friends, &settings).unwrap();
+        assert!(config.sheet_urls.is_empty());
+        assert_eq!(config.friends.len(), 1);
+    }
+}
@@ -0,0 +1,250 @@
+// The optional config.toml, an alternative to the friend sheet and a long
+// list of env vars:
+//
+//     messenger = "whapi"
+//
+//     [whapi]
+//     token = "…"
+//     group_id = "120363…@g.us"
+//
+//     [lookback]
+//     boundary = "calendar"
+//     timezone = "Europe/London"
+//
+//     [settings]
+//     show_goals = true
+//     quiet_until = 2026-12-24
+//
+//     [[friends]]
+//     name = "Luke"
+//     username = "lukesanderson"
+//     emoji = "🇬🇧"
+//
+// [settings] takes any other option under its env var's name, in either
+// case. An env var that is set always wins over the file.
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// One `[[friends]]` entry, standing in for a sheet row.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FriendEntry {
+    pub name: String,
+    pub username: String,
+    // Shown before the name, like the sheet's third column
+    #[serde(alias = "emoji")]
+    pub flair: Option<String>,
+    // Shown instead of `name` in the digest
+    pub alias: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct WhapiSection {
+    token: Option<String>,
+    group_id: Option<String>,
+    admin_chat_id: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct TelegramSection {
+    bot_token: Option<String>,
+    chat_id: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct DiscordSection {
+    webhook_url: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct LookbackSection {
+    boundary: Option<String>,
+    start_day: Option<String>,
+    timezone: Option<String>,
+    since_last_send: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    messenger: Option<String>,
+    #[serde(default)]
+    whapi: WhapiSection,
+    #[serde(default)]
+    telegram: TelegramSection,
+    #[serde(default)]
+    discord: DiscordSection,
+    #[serde(default)]
+    lookback: LookbackSection,
+    #[serde(default)]
+    settings: HashMap<String, toml::Value>,
+    #[serde(default)]
+    pub friends: Vec<FriendEntry>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<Self> {
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read config file {}", path))?;
+        Self::parse(&raw).with_context(|| format!("Config file {} is invalid", path))
+    }
+
+    fn parse(raw: &str) -> Result<Self> {
+        Ok(toml::from_str(raw)?)
+    }
+
+    /// Everything but the friends, keyed by env var name with values as the
+    /// env var would hold them.
+    pub fn settings(&self) -> Result<HashMap<String, String>> {
+        let mut settings = HashMap::new();
+        for (key, value) in &self.settings {
+            settings.insert(key.to_uppercase(), env_value(key, value)?);
+        }
+
+        let lookback = &self.lookback;
+        let typed = [
+            ("MESSENGER", self.messenger.clone()),
+            ("WHAPI_TOKEN", self.whapi.token.clone()),
+            ("GROUP_ID", self.whapi.group_id.clone()),
+            ("ADMIN_CHAT_ID", self.whapi.admin_chat_id.clone()),
+            ("TELEGRAM_BOT_TOKEN", self.telegram.bot_token.clone()),
+            ("TELEGRAM_CHAT_ID", self.telegram.chat_id.clone()),
+            ("DISCORD_WEBHOOK_URL", self.discord.webhook_url.clone()),
+            ("WEEK_BOUNDARY", lookback.boundary.clone()),
+            ("WEEK_START_DAY", lookback.start_day.clone()),
+            ("TIMEZONE", lookback.timezone.clone()),
+            (
+                "SINCE_LAST_SEND",
+                lookback.since_last_send.map(|on| on.to_string()),
+            ),
+        ];
+        for (key, value) in typed {
+            let Some(value) = value else {
+                continue;
+            };
+            if settings.insert(key.to_string(), value).is_some() {
+                bail!("{} is set both in [settings] and in its own section", key);
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Strings as they are, dates and other scalars as written, and arrays
+/// comma-separated like SHEET_CSV_URL.
+fn env_value(key: &str, value: &toml::Value) -> Result<String> {
+    Ok(match value {
+        toml::Value::String(text) => text.clone(),
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::Float(x) => x.to_string(),
+        toml::Value::Boolean(on) => on.to_string(),
+        toml::Value::Datetime(date) => date.to_string(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(|item| env_value(key, item))
+            .collect::<Result<Vec<_>>>()?
+            .join(","),
+        toml::Value::Table(_) => bail!("settings.{} can't be a table", key),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_map_onto_their_env_vars() {
+        let file = ConfigFile::parse(
+            r#"
+            messenger = "telegram"
+            [whapi]
+            token = "secret"
+            group_id = "123@g.us"
+            [telegram]
+            bot_token = "bot"
+            [lookback]
+            boundary = "calendar"
+            since_last_send = true
+            "#,
+        )
+        .unwrap();
+        let settings = file.settings().unwrap();
+        assert_eq!(settings["MESSENGER"], "telegram");
+        assert_eq!(settings["WHAPI_TOKEN"], "secret");
+        assert_eq!(settings["GROUP_ID"], "123@g.us");
+        assert_eq!(settings["TELEGRAM_BOT_TOKEN"], "bot");
+        assert_eq!(settings["WEEK_BOUNDARY"], "calendar");
+        assert_eq!(settings["SINCE_LAST_SEND"], "true");
+    }
+
+    #[test]
+    fn settings_take_any_toml_value() {
+        let file = ConfigFile::parse(
+            r#"
+            [settings]
+            show_goals = true
+            leaderboard_size = 5
+            quiet_until = 2026-12-24
+            SHEET_CSV_URL = [
+                "https://one.example/sheet.csv",
+                "https://two.example/sheet.csv",
+            ]
+            "#,
+        )
+        .unwrap();
+        let settings = file.settings().unwrap();
+        assert_eq!(settings["SHOW_GOALS"], "true");
+        assert_eq!(settings["LEADERBOARD_SIZE"], "5");
+        assert_eq!(settings["QUIET_UNTIL"], "2026-12-24");
+        assert_eq!(
+            settings["SHEET_CSV_URL"],
+            "https://one.example/sheet.csv,https://two.example/sheet.csv"
+        );
+    }
+
+    #[test]
+    fn friends_accept_emoji_or_flair_and_an_alias() {
+        let file = ConfigFile::parse(
+            r#"
+            [[friends]]
+            name = "Luke Sanderson"
+            username = "lukesanderson"
+            emoji = "🇬🇧"
+            alias = "Luke"
+
+            [[friends]]
+            name = "Ann"
+            username = "ann"
+            flair = "✨"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.friends.len(), 2);
+        assert_eq!(file.friends[0].flair.as_deref(), Some("🇬🇧"));
+        assert_eq!(file.friends[0].alias.as_deref(), Some("Luke"));
+        assert_eq!(file.friends[1].flair.as_deref(), Some("✨"));
+        assert_eq!(file.friends[1].alias, None);
+    }
+
+    #[test]
+    fn unknown_keys_and_incomplete_friends_are_errors() {
+        assert!(ConfigFile::parse("[whapi]\ntokn = \"x\"").is_err());
+        assert!(ConfigFile::parse("[[friends]]\nname = \"Ann\"").is_err());
+        assert!(
+            ConfigFile::parse("[[friends]]\nname = \"A\"\nusername = \"a\"\nnick = \"x\"").is_err()
+        );
+    }
+
+    #[test]
+    fn a_setting_given_twice_is_an_error() {
+        let file =
+            ConfigFile::parse("[settings]\ngroup_id = \"a\"\n[whapi]\ngroup_id = \"b\"").unwrap();
+        assert!(file.settings().is_err());
+    }
+}
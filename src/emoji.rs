@@ -0,0 +1,176 @@
+use anyhow::{Result, bail};
+use std::str::FromStr;
+
+/// The reaction shown for each rating band.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmojiSet {
+    /// Exactly 5 stars
+    pub perfect: String,
+    /// 4 to 4.5 stars
+    pub great: String,
+    /// 3 to 3.5 stars
+    pub good: String,
+    /// 2 to 2.5 stars
+    pub okay: String,
+    /// Half a star to 1.5 stars
+    pub bad: String,
+    /// No usable rating
+    pub unrated: String,
+}
+
+impl EmojiSet {
+    fn from_bands(bands: [&str; 6]) -> Self {
+        let [perfect, great, good, okay, bad, unrated] = bands.map(str::to_string);
+        EmojiSet {
+            perfect,
+            great,
+            good,
+            okay,
+            bad,
+            unrated,
+        }
+    }
+
+    pub fn for_theme(theme: EmojiTheme) -> Self {
+        match theme {
+            EmojiTheme::Classic => Self::from_bands(["🤩", "🔥", "🙂", "😐", "🤮", "🤔"]),
+            EmojiTheme::Cinema => Self::from_bands(["🏆", "🎬", "🎟️", "🍿", "💤", "❔"]),
+            EmojiTheme::Foodie => Self::from_bands(["🤤", "😋", "👍", "😐", "🤢", "🍽️"]),
+            EmojiTheme::Minimal => Self::from_bands(["!!", "!", "+", "~", "-", "?"]),
+        }
+    }
+
+    pub fn for_score(&self, score: f32) -> &str {
+        if score == 5.0 {
+            &self.perfect
+        } else if score >= 4.0 {
+            &self.great
+        } else if score >= 3.0 {
+            &self.good
+        } else if score >= 2.0 {
+            &self.okay
+        } else if score > 0.0 {
+            &self.bad
+        } else {
+            &self.unrated
+        }
+    }
+
+    /// Applies an EMOJI_MAP such as "perfect=🏆,bad=💤" on top of this set.
+    /// Bands that aren't mentioned keep their current emoji.
+    pub fn with_overrides(mut self, map: &str) -> Result<Self> {
+        for pair in map.split(',').filter(|p| !p.trim().is_empty()) {
+            let Some((band, emoji)) = pair.split_once('=') else {
+                bail!("EMOJI_MAP entry '{}' should look like band=emoji", pair);
+            };
+            let slot = match band.trim().to_lowercase().as_str() {
+                "perfect" => &mut self.perfect,
+                "great" => &mut self.great,
+                "good" => &mut self.good,
+                "okay" => &mut self.okay,
+                "bad" => &mut self.bad,
+                "unrated" => &mut self.unrated,
+                other => bail!(
+                    "Unknown EMOJI_MAP band '{}', expected perfect, great, good, okay, bad or unrated",
+                    other
+                ),
+            };
+            *slot = emoji.trim().to_string();
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EmojiTheme {
+    Classic,
+    Cinema,
+    Foodie,
+    Minimal,
+}
+
+impl FromStr for EmojiTheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "classic" => Ok(EmojiTheme::Classic),
+            "cinema" => Ok(EmojiTheme::Cinema),
+            "foodie" => Ok(EmojiTheme::Foodie),
+            "minimal" => Ok(EmojiTheme::Minimal),
+            other => bail!(
+                "Unknown EMOJI_THEME '{}', expected classic, cinema, foodie or minimal",
+                other
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One score per band: perfect, great, good, okay, bad, unrated
+    const BAND_SCORES: [f32; 6] = [5.0, 4.5, 3.0, 2.5, 0.5, 0.0];
+
+    fn bands(theme: EmojiTheme) -> Vec<String> {
+        let set = EmojiSet::for_theme(theme);
+        BAND_SCORES
+            .iter()
+            .map(|&score| set.for_score(score).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn the_classic_theme_keeps_the_original_emoji() {
+        assert_eq!(
+            bands(EmojiTheme::Classic),
+            ["🤩", "🔥", "🙂", "😐", "🤮", "🤔"]
+        );
+    }
+
+    #[test]
+    fn the_cinema_theme_fills_each_band() {
+        assert_eq!(
+            bands(EmojiTheme::Cinema),
+            ["🏆", "🎬", "🎟️", "🍿", "💤", "❔"]
+        );
+    }
+
+    #[test]
+    fn the_foodie_theme_fills_each_band() {
+        assert_eq!(
+            bands(EmojiTheme::Foodie),
+            ["🤤", "😋", "👍", "😐", "🤢", "🍽️"]
+        );
+    }
+
+    #[test]
+    fn the_minimal_theme_fills_each_band() {
+        assert_eq!(bands(EmojiTheme::Minimal), ["!!", "!", "+", "~", "-", "?"]);
+    }
+
+    #[test]
+    fn each_band_starts_at_its_lower_edge() {
+        let set = EmojiSet::for_theme(EmojiTheme::Classic);
+        assert_eq!(set.for_score(4.0), "🔥");
+        assert_eq!(set.for_score(3.5), "🙂");
+        assert_eq!(set.for_score(2.0), "😐");
+        assert_eq!(set.for_score(1.5), "🤮");
+    }
+
+    #[test]
+    fn emoji_map_overrides_only_the_bands_it_names() {
+        let set = EmojiSet::for_theme(EmojiTheme::Classic)
+            .with_overrides("perfect=🏆, bad = 💤")
+            .unwrap();
+        assert_eq!(set.perfect, "🏆");
+        assert_eq!(set.bad, "💤");
+        assert_eq!(set.great, "🔥");
+        assert!(
+            EmojiSet::for_theme(EmojiTheme::Classic)
+                .with_overrides("best=🏆")
+                .is_err()
+        );
+    }
+}
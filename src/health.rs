@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::net::TcpListener;
+
+/// Answers liveness and readiness probes until the process exits:
+/// `/healthz` is always 200, `/readyz` is 200 once `ready` is set.
+pub async fn serve(port: u16, ready: Arc<AtomicBool>) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))
+        .await
+        .with_context(|| format!("Couldn't listen for health checks on port {}", port))?;
+    axum::serve(listener, router(ready))
+        .await
+        .context("Health check server stopped")
+}
+
+fn router(ready: Arc<AtomicBool>) -> Router {
+    Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/readyz", get(readyz))
+        .with_state(ready)
+}
+
+async fn readyz(State(ready): State<Arc<AtomicBool>>) -> (StatusCode, &'static str) {
+    if ready.load(Ordering::Relaxed) {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "no successful run yet")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn readiness_follows_the_flag_and_liveness_is_always_up() {
+        let ready = Arc::new(AtomicBool::new(false));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(axum::serve(listener, router(ready.clone())).into_future());
+
+        let client = reqwest::Client::new();
+        let get = |path: &str| client.get(format!("{}{}", base, path)).send();
+        assert_eq!(get("/healthz").await.unwrap().status(), 200);
+        let readyz = get("/readyz").await.unwrap();
+        assert_eq!(readyz.status(), 503);
+        assert_eq!(readyz.text().await.unwrap(), "no successful run yet");
+
+        ready.store(true, Ordering::Relaxed);
+        assert_eq!(get("/readyz").await.unwrap().status(), 200);
+        assert_eq!(
+            client
+                .post(format!("{}/healthz", base))
+                .send()
+                .await
+                .unwrap()
+                .status(),
+            405
+        );
+        assert_eq!(get("/metrics").await.unwrap().status(), 404);
+    }
+}
@@ -0,0 +1,3 @@
+// The parts of the bot that stand alone, shared with the benchmarks.
+
+pub mod sheet;
@@ -0,0 +1,62 @@
+/// The bits of syntax that differ between the places a digest is posted.
+/// Renderers build the same structure and ask this for the formatting.
+pub trait Markup {
+    fn bold(&self, text: &str) -> String;
+    fn italic(&self, text: &str) -> String;
+    /// The digest's top heading
+    fn heading(&self, text: &str) -> String;
+    /// The first line(s) of a film block: title, any plain-text details
+    /// after it (e.g. " – 2h16m"), plus its link
+    fn film_heading(&self, title: &str, details: &str, link: &str) -> String;
+    fn bullet(&self) -> &'static str;
+}
+
+/// WhatsApp's flavour: *bold*, _italic_, bare links.
+pub struct WhatsApp;
+
+impl Markup for WhatsApp {
+    fn bold(&self, text: &str) -> String {
+        format!("*{}*", text)
+    }
+
+    fn italic(&self, text: &str) -> String {
+        format!("_{}_", text)
+    }
+
+    fn heading(&self, text: &str) -> String {
+        self.bold(text)
+    }
+
+    fn film_heading(&self, title: &str, details: &str, link: &str) -> String {
+        format!("🎬 {}{}\n{}\n", self.bold(title), details, link)
+    }
+
+    fn bullet(&self) -> &'static str {
+        "• "
+    }
+}
+
+/// Plain Markdown, for piping into static sites and the like.
+pub struct Markdown;
+
+impl Markup for Markdown {
+    fn bold(&self, text: &str) -> String {
+        format!("**{}**", text)
+    }
+
+    fn italic(&self, text: &str) -> String {
+        format!("_{}_", text)
+    }
+
+    fn heading(&self, text: &str) -> String {
+        format!("## {}", text)
+    }
+
+    fn film_heading(&self, title: &str, details: &str, link: &str) -> String {
+        format!("### 🎬 [{}]({}){}\n", title, link, details)
+    }
+
+    fn bullet(&self) -> &'static str {
+        "- "
+    }
+}
@@ -0,0 +1,89 @@
+use crate::pacer::Pacer;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use tracing::warn;
+
+// Lifetime totals only need to be roughly right, so don't re-scrape often
+const CACHE_TTL_DAYS: i64 = 30;
+
+static FILMS_STAT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<span class="value">([\d,]+)</span>\s*<span class="definition">Films</span>"#)
+        .expect("valid films statistic regex")
+});
+
+#[derive(Serialize, Deserialize)]
+struct CachedStat {
+    films: u32,
+    fetched_at: DateTime<Utc>,
+}
+
+/// Total films logged per username, scraped from profile pages. Anything
+/// that can't be fetched is simply left out.
+pub async fn fetch_member_stats(
+    client: &Client,
+    profile_urls: &[(String, String)],
+    cache_path: Option<&str>,
+    now: DateTime<Utc>,
+    pacer: &Pacer,
+) -> HashMap<String, u32> {
+    let mut cache: HashMap<String, CachedStat> = cache_path
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default();
+
+    let mut stats = HashMap::new();
+    for (username, url) in profile_urls {
+        if let Some(cached) = cache.get(username)
+            && now - cached.fetched_at < Duration::days(CACHE_TTL_DAYS)
+        {
+            stats.insert(username.clone(), cached.films);
+            continue;
+        }
+
+        pacer.wait().await;
+        match fetch_films_logged(client, url).await {
+            Ok(films) => {
+                stats.insert(username.clone(), films);
+                cache.insert(
+                    username.clone(),
+                    CachedStat {
+                        films,
+                        fetched_at: now,
+                    },
+                );
+            }
+            Err(e) => warn!(username = %username, error = %e, "Couldn't read profile stats"),
+        }
+    }
+
+    if let Some(path) = cache_path
+        && let Err(e) = save_cache(path, &cache)
+    {
+        warn!(path = %path, error = %e, "Couldn't save the member stats cache");
+    }
+
+    stats
+}
+
+async fn fetch_films_logged(client: &Client, url: &str) -> Result<u32> {
+    let response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        anyhow::bail!("HTTP {}", response.status());
+    }
+
+    let html = response.text().await?;
+    let caps = FILMS_STAT_REGEX
+        .captures(&html)
+        .context("No films statistic on the profile page")?;
+    Ok(caps[1].replace(',', "").parse()?)
+}
+
+fn save_cache(path: &str, cache: &HashMap<String, CachedStat>) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(cache)?)?;
+    Ok(())
+}
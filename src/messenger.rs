@@ -0,0 +1,538 @@
+use crate::config::{Config, MessengerKind};
+use crate::markup::{self, Markup};
+use anyhow::{Context, Result, bail};
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use serde_json::Value;
+use tracing::{info, warn};
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+// Telegram caps text at 4096 like WhatsApp; Discord at 2000
+const TELEGRAM_MAX_CHARS: usize = 4096;
+const DISCORD_MAX_CHARS: usize = 2000;
+// Discord's SUPPRESS_EMBEDS flag, so a digest isn't followed by a card per link
+const DISCORD_SUPPRESS_EMBEDS: u64 = 1 << 2;
+
+/// Somewhere a digest can be delivered. Methods hand back boxed futures so
+/// the backend picked by MESSENGER can be held as a `dyn Messenger`.
+pub trait Messenger: Send + Sync {
+    /// The chat messages go to, as used in idempotency keys and the sent log.
+    fn destination(&self) -> &str;
+    fn markup(&self) -> &'static dyn Markup;
+    /// Longest text the platform accepts in one message.
+    fn max_message_chars(&self) -> usize;
+    fn supports_pinning(&self) -> bool;
+
+    /// Sends a text message, quoting `reply_to` where the platform can, and
+    /// returns the new message's id.
+    fn send_text<'a>(
+        &'a self,
+        text: &'a str,
+        reply_to: Option<&'a str>,
+        idempotency_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>>;
+
+    /// Sends a file, as an image for image types and a document otherwise.
+    fn send_media<'a>(
+        &'a self,
+        mime: &'a str,
+        bytes: &'a [u8],
+        filename: &'a str,
+        caption: &'a str,
+    ) -> BoxFuture<'a, Result<String>>;
+
+    /// Only called when `supports_pinning` is true.
+    fn pin<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Anything left to do once the whole digest is out.
+    fn finish(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// The backend MESSENGER selects, with the credentials `Config` checked for it.
+pub fn from_config(client: &Client, config: &Config) -> Box<dyn Messenger> {
+    match config.messenger {
+        MessengerKind::Whapi => Box::new(Whapi {
+            client: client.clone(),
+            token: config.whapi_token.clone(),
+            chat_id: config.group_id.clone(),
+        }),
+        MessengerKind::Telegram => Box::new(Telegram {
+            client: client.clone(),
+            token: config.telegram_bot_token.clone(),
+            chat_id: config.telegram_chat_id.clone(),
+        }),
+        MessengerKind::Discord => Box::new(Discord {
+            client: client.clone(),
+            destination: webhook_destination(&config.discord_webhook_url),
+            webhook_url: config.discord_webhook_url.clone(),
+        }),
+    }
+}
+
+/// WhatsApp through Whapi, the original backend.
+pub struct Whapi {
+    client: Client,
+    token: String,
+    chat_id: String,
+}
+
+impl Messenger for Whapi {
+    fn destination(&self) -> &str {
+        &self.chat_id
+    }
+
+    fn markup(&self) -> &'static dyn Markup {
+        &markup::WhatsApp
+    }
+
+    fn max_message_chars(&self) -> usize {
+        crate::MAX_MESSAGE_CHARS
+    }
+
+    fn supports_pinning(&self) -> bool {
+        true
+    }
+
+    fn send_text<'a>(
+        &'a self,
+        text: &'a str,
+        reply_to: Option<&'a str>,
+        idempotency_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(crate::send_whatsapp(
+            &self.client,
+            text,
+            reply_to,
+            idempotency_key,
+            &self.token,
+            &self.chat_id,
+        ))
+    }
+
+    fn send_media<'a>(
+        &'a self,
+        mime: &'a str,
+        bytes: &'a [u8],
+        filename: &'a str,
+        caption: &'a str,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(crate::send_media(
+            &self.client,
+            mime,
+            bytes,
+            filename,
+            caption,
+            &self.token,
+            &self.chat_id,
+        ))
+    }
+
+    fn pin<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(crate::pin_message(&self.client, message_id, &self.token))
+    }
+
+    fn finish(&self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(crate::set_presence_offline(&self.client, &self.token))
+    }
+}
+
+/// A Telegram bot posting to one chat. Text goes out as legacy Markdown,
+/// whose *bold* and _italic_ match the WhatsApp markup.
+pub struct Telegram {
+    client: Client,
+    token: String,
+    chat_id: String,
+}
+
+impl Telegram {
+    async fn call(&self, method: &str, request: reqwest::RequestBuilder) -> Result<Value> {
+        let response = request.send().await?;
+        let status = response.status();
+        let body: Value = response.json().await.with_context(|| {
+            format!(
+                "Telegram {} answered with something other than JSON",
+                method
+            )
+        })?;
+        if body.get("ok").and_then(Value::as_bool) != Some(true) {
+            bail!(
+                "Telegram {} failed! Status: {}. Details: {}",
+                method,
+                status,
+                body.get("description")
+                    .and_then(Value::as_str)
+                    .unwrap_or("No error details provided")
+            );
+        }
+        Ok(body)
+    }
+
+    fn url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", TELEGRAM_API, self.token, method)
+    }
+
+    async fn send_message(&self, text: &str, reply_to: Option<&str>) -> Result<String> {
+        let mut payload = serde_json::json!({
+            "chat_id": self.chat_id,
+            "text": text,
+            "parse_mode": "Markdown",
+            "link_preview_options": { "is_disabled": true },
+        });
+        if let Some(reply_to) = reply_to.and_then(|id| id.parse::<i64>().ok()) {
+            payload["reply_parameters"] = serde_json::json!({ "message_id": reply_to });
+        }
+
+        let sent = self
+            .call(
+                "sendMessage",
+                self.client.post(self.url("sendMessage")).json(&payload),
+            )
+            .await;
+        // A stray '_' or '*' in a name or review breaks Markdown parsing,
+        // so send those as plain text rather than not at all
+        let body = match sent {
+            Err(e) if e.to_string().contains("can't parse entities") => {
+                warn!("Telegram couldn't parse the formatting, sending it as plain text");
+                if let Some(fields) = payload.as_object_mut() {
+                    fields.remove("parse_mode");
+                }
+                self.call(
+                    "sendMessage",
+                    self.client.post(self.url("sendMessage")).json(&payload),
+                )
+                .await?
+            }
+            other => other?,
+        };
+
+        info!("Sent the Telegram message");
+        telegram_message_id(&body)
+    }
+
+    async fn send_file(
+        &self,
+        mime: &str,
+        bytes: &[u8],
+        filename: &str,
+        caption: &str,
+    ) -> Result<String> {
+        let (method, field) = if mime.starts_with("image/") {
+            ("sendPhoto", "photo")
+        } else {
+            ("sendDocument", "document")
+        };
+        let form = Form::new()
+            .text("chat_id", self.chat_id.clone())
+            .text("caption", caption.to_string())
+            .part(field, file_part(mime, bytes, filename)?);
+        let request = self.client.post(self.url(method)).multipart(form);
+        let body = self.call(method, request).await?;
+
+        info!(file = %filename, "Sent the file through Telegram");
+        telegram_message_id(&body)
+    }
+
+    async fn pin_message(&self, message_id: &str) -> Result<()> {
+        let message_id: i64 = message_id
+            .parse()
+            .with_context(|| format!("'{}' isn't a Telegram message id", message_id))?;
+        let payload = serde_json::json!({
+            "chat_id": self.chat_id,
+            "message_id": message_id,
+            "disable_notification": true,
+        });
+        self.call(
+            "pinChatMessage",
+            self.client.post(self.url("pinChatMessage")).json(&payload),
+        )
+        .await?;
+
+        info!("Pinned the message");
+        Ok(())
+    }
+}
+
+impl Messenger for Telegram {
+    fn destination(&self) -> &str {
+        &self.chat_id
+    }
+
+    fn markup(&self) -> &'static dyn Markup {
+        &markup::WhatsApp
+    }
+
+    fn max_message_chars(&self) -> usize {
+        TELEGRAM_MAX_CHARS
+    }
+
+    fn supports_pinning(&self) -> bool {
+        true
+    }
+
+    // Telegram has no idempotency key; the state file's ledger still
+    // stops a rerun from sending a chunk twice
+    fn send_text<'a>(
+        &'a self,
+        text: &'a str,
+        reply_to: Option<&'a str>,
+        _idempotency_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.send_message(text, reply_to))
+    }
+
+    fn send_media<'a>(
+        &'a self,
+        mime: &'a str,
+        bytes: &'a [u8],
+        filename: &'a str,
+        caption: &'a str,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.send_file(mime, bytes, filename, caption))
+    }
+
+    fn pin<'a>(&'a self, message_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.pin_message(message_id))
+    }
+}
+
+fn telegram_message_id(body: &Value) -> Result<String> {
+    body.get("result")
+        .and_then(|result| result.get("message_id"))
+        .and_then(Value::as_i64)
+        .map(|id| id.to_string())
+        .context("Could not parse Message ID from the Telegram response")
+}
+
+/// A Discord channel webhook. Webhooks can't reply to or pin messages, so
+/// a threaded digest arrives as a plain sequence.
+pub struct Discord {
+    client: Client,
+    webhook_url: String,
+    // The webhook's id, since the URL itself holds its secret token
+    destination: String,
+}
+
+/// "discord-<id>" from ".../api/webhooks/<id>/<token>", or a hash of the
+/// URL when it doesn't look like that, so the token never reaches the state
+/// file or the sent log.
+fn webhook_destination(webhook_url: &str) -> String {
+    let id = webhook_url
+        .split_once("/webhooks/")
+        .and_then(|(_, rest)| rest.split('/').next())
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()));
+    match id {
+        Some(id) => format!("discord-{}", id),
+        None => format!("discord-{:016x}", crate::content_hash(webhook_url)),
+    }
+}
+
+impl Discord {
+    // `wait=true` makes Discord answer with the created message
+    fn url(&self) -> String {
+        let separator = if self.webhook_url.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        format!("{}{}wait=true", self.webhook_url, separator)
+    }
+
+    async fn execute(&self, request: reqwest::RequestBuilder, what: &str) -> Result<String> {
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_body = response.text().await.unwrap_or_default();
+            bail!(
+                "Discord {} failed! Status: {}. Details: {}",
+                what,
+                status,
+                error_body
+            );
+        }
+
+        let body: Value = response.json().await?;
+        body.get("id")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .context("Could not parse Message ID from the Discord response")
+    }
+
+    fn payload(&self, content: &str) -> Value {
+        serde_json::json!({
+            "content": content,
+            "flags": DISCORD_SUPPRESS_EMBEDS,
+            // Never ping anyone, whatever ends up in a review
+            "allowed_mentions": { "parse": [] },
+        })
+    }
+
+    async fn send_message(&self, text: &str) -> Result<String> {
+        let request = self.client.post(self.url()).json(&self.payload(text));
+        let id = self.execute(request, "message").await?;
+        info!("Sent the Discord message");
+        Ok(id)
+    }
+
+    async fn send_file(
+        &self,
+        mime: &str,
+        bytes: &[u8],
+        filename: &str,
+        caption: &str,
+    ) -> Result<String> {
+        let form = Form::new()
+            .text("payload_json", self.payload(caption).to_string())
+            .part("files[0]", file_part(mime, bytes, filename)?);
+        let request = self.client.post(self.url()).multipart(form);
+        let id = self.execute(request, "upload").await?;
+        info!(file = %filename, "Sent the file through Discord");
+        Ok(id)
+    }
+}
+
+impl Messenger for Discord {
+    fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    fn markup(&self) -> &'static dyn Markup {
+        &markup::Markdown
+    }
+
+    fn max_message_chars(&self) -> usize {
+        DISCORD_MAX_CHARS
+    }
+
+    fn supports_pinning(&self) -> bool {
+        false
+    }
+
+    fn send_text<'a>(
+        &'a self,
+        text: &'a str,
+        _reply_to: Option<&'a str>,
+        _idempotency_key: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.send_message(text))
+    }
+
+    fn send_media<'a>(
+        &'a self,
+        mime: &'a str,
+        bytes: &'a [u8],
+        filename: &'a str,
+        caption: &'a str,
+    ) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.send_file(mime, bytes, filename, caption))
+    }
+
+    fn pin<'a>(&'a self, _message_id: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { bail!("Discord webhooks can't pin messages") })
+    }
+}
+
+/// The uploaded file of a multipart form.
+fn file_part(mime: &str, bytes: &[u8], filename: &str) -> Result<Part> {
+    Part::bytes(bytes.to_vec())
+        .file_name(filename.to_string())
+        .mime_str(mime)
+        .with_context(|| format!("'{}' isn't a valid MIME type", mime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Answers one request with `{"id": "42"}` and hands back the raw request.
+    async fn capture_one() -> (String, tokio::task::JoinHandle<Vec<u8>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let request = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                let read = socket.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..read]);
+                let text = String::from_utf8_lossy(&request);
+                let Some(head_end) = text.find("\r\n\r\n") else {
+                    continue;
+                };
+                let length: usize = text[..head_end]
+                    .lines()
+                    .find_map(|line| {
+                        line.to_lowercase()
+                            .strip_prefix("content-length: ")
+                            .map(str::to_string)
+                    })
+                    .and_then(|len| len.trim().parse().ok())
+                    .unwrap_or(0);
+                if read == 0 || request.len() >= head_end + 4 + length {
+                    break;
+                }
+            }
+            let body = r#"{"id": "42"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            request
+        });
+        (url, request)
+    }
+
+    #[tokio::test]
+    async fn discord_uploads_are_multipart_forms() {
+        let (url, request) = capture_one().await;
+        let discord = Discord {
+            client: Client::new(),
+            webhook_url: url,
+            destination: "discord-1".to_string(),
+        };
+
+        let bytes = b"\xFF\xD8 not really a jpeg";
+        let id = discord
+            .send_file("image/jpeg", bytes, "poster \"collage\".jpg", "This week")
+            .await
+            .unwrap();
+        assert_eq!(id, "42");
+
+        let request = request.await.unwrap();
+        let text = String::from_utf8_lossy(&request);
+        assert!(text.starts_with("POST /webhook?wait=true "));
+        assert!(text.contains("content-type: multipart/form-data; boundary="));
+        assert!(text.contains("name=\"payload_json\"\r\n\r\n{"));
+        assert!(text.contains("\"content\":\"This week\""));
+        // The quotes in the filename are escaped rather than ending the header
+        assert!(text.contains(r#"name="files[0]"; filename="poster \"collage\".jpg""#));
+        assert!(text.contains("Content-Type: image/jpeg\r\n\r\n"));
+        assert!(request.windows(bytes.len()).any(|window| window == bytes));
+    }
+
+    #[test]
+    fn a_bad_mime_type_is_an_error() {
+        assert!(file_part("not a mime", b"x", "x.bin").is_err());
+    }
+
+    #[test]
+    fn webhook_destination_keeps_the_id_and_drops_the_token() {
+        let url = "https://discord.com/api/webhooks/123456789/s3cr3t-t0ken";
+        assert_eq!(webhook_destination(url), "discord-123456789");
+    }
+
+    #[test]
+    fn webhook_destination_hashes_unrecognised_urls() {
+        let url = "https://proxy.example/hook?token=s3cr3t";
+        let destination = webhook_destination(url);
+        assert!(destination.starts_with("discord-"));
+        assert!(!destination.contains("s3cr3t"));
+        assert_eq!(destination, webhook_destination(url));
+    }
+}
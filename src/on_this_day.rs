@@ -0,0 +1,61 @@
+use chrono::{Datelike, NaiveDate};
+
+/// Well-known films by (month, day, year) of their original release. Kept
+/// small on purpose: days without an entry just get no footer.
+const RELEASES: &[(u32, u32, i32, &str)] = &[
+    (2, 12, 1993, "Groundhog Day"),
+    (2, 14, 1991, "The Silence of the Lambs"),
+    (2, 16, 2018, "Black Panther"),
+    (2, 24, 2017, "Get Out"),
+    (3, 24, 1972, "The Godfather"),
+    (3, 31, 1999, "The Matrix"),
+    (4, 26, 2019, "Avengers: Endgame"),
+    (4, 30, 2004, "Mean Girls"),
+    (5, 9, 1980, "Friday the 13th"),
+    (5, 15, 2015, "Mad Max: Fury Road"),
+    (5, 16, 1986, "Top Gun"),
+    (5, 18, 2001, "Shrek"),
+    (5, 21, 1980, "The Empire Strikes Back"),
+    (5, 23, 1980, "The Shining"),
+    (5, 25, 1977, "Star Wars"),
+    (5, 30, 2003, "Finding Nemo"),
+    (6, 8, 1984, "Ghostbusters"),
+    (6, 11, 1982, "E.T. the Extra-Terrestrial"),
+    (6, 12, 1981, "Raiders of the Lost Ark"),
+    (6, 20, 1975, "Jaws"),
+    (6, 25, 1982, "Blade Runner"),
+    (7, 3, 1985, "Back to the Future"),
+    (7, 6, 1994, "Forrest Gump"),
+    (
+        7,
+        9,
+        2003,
+        "Pirates of the Caribbean: The Curse of the Black Pearl",
+    ),
+    (7, 16, 2010, "Inception"),
+    (7, 18, 2008, "The Dark Knight"),
+    (7, 20, 2001, "Spirited Away"),
+    (7, 21, 2023, "Oppenheimer"),
+    (8, 25, 1939, "The Wizard of Oz"),
+    (10, 1, 2010, "The Social Network"),
+    (10, 4, 2013, "Gravity"),
+    (10, 14, 1994, "Pulp Fiction"),
+    (10, 15, 1999, "Fight Club"),
+    (10, 25, 1978, "Halloween"),
+    (10, 26, 1984, "The Terminator"),
+    (11, 16, 1990, "Home Alone"),
+    (11, 22, 1995, "Toy Story"),
+    (11, 26, 1942, "Casablanca"),
+    (11, 27, 2013, "Frozen"),
+    (12, 18, 2009, "Avatar"),
+    (12, 19, 1997, "Titanic"),
+    (12, 20, 1996, "Scream"),
+];
+
+/// The film released on this month and day, as (year, title).
+pub fn release_on(date: NaiveDate) -> Option<(i32, &'static str)> {
+    RELEASES
+        .iter()
+        .find(|(month, day, _, _)| *month == date.month() && *day == date.day())
+        .map(|(_, _, year, title)| (*year, *title))
+}
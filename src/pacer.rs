@@ -0,0 +1,40 @@
+use rand::Rng;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Spaces out the *start* of requests to the same host. Callers share one
+/// pacer, so the spacing holds no matter how many fetches are in flight.
+pub struct Pacer {
+    delay: Duration,
+    jitter: Duration,
+    next_start: Mutex<Instant>,
+}
+
+impl Pacer {
+    pub fn new(delay: Duration, jitter: Duration) -> Self {
+        Pacer {
+            delay,
+            jitter,
+            next_start: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for this caller's slot. A zero delay never waits.
+    pub async fn wait(&self) {
+        if self.delay.is_zero() && self.jitter.is_zero() {
+            return;
+        }
+
+        // Holding the lock while sleeping is what queues the callers up
+        let mut next_start = self.next_start.lock().await;
+        tokio::time::sleep_until(*next_start).await;
+
+        let jitter_ms = if self.jitter.is_zero() {
+            0
+        } else {
+            rand::rng().random_range(0..=self.jitter.as_millis() as u64)
+        };
+        *next_start = Instant::now() + self.delay + Duration::from_millis(jitter_ms);
+    }
+}
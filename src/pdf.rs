@@ -0,0 +1,309 @@
+// A deliberately tiny PDF writer: one base-14 font, plain text lines, A4 pages.
+// The built-in Helvetica only covers WinAnsi (roughly Latin-1), so stars are
+// turned into plain words first (see `pdf_safe`), letters from other scripts
+// are transliterated, emoji are dropped, and long lines wrap to the page.
+
+use anyhow::{Result, bail};
+
+const PAGE_WIDTH: u32 = 595;
+const PAGE_HEIGHT: u32 = 842;
+const MARGIN: u32 = 50;
+const FONT_SIZE: u32 = 11;
+const LINE_HEIGHT: u32 = 15;
+const LINES_PER_PAGE: usize = ((PAGE_HEIGHT - 2 * MARGIN) / LINE_HEIGHT) as usize;
+// Usable line width in thousandths of the font size, the unit of `glyph_width`
+const LINE_WIDTH: u32 = (PAGE_WIDTH - 2 * MARGIN) * 1000 / FONT_SIZE;
+// Wrapped lines are indented so they read as part of the line above
+const CONTINUATION_INDENT: &[u8] = b"    ";
+
+/// Lays the lines out top to bottom, wrapping any wider than the page and
+/// starting a new page when one fills up. Fails on a letter that has neither
+/// a WinAnsi glyph nor a transliteration, so the caller can send text instead.
+pub fn render_lines(lines: &[String]) -> Result<Vec<u8>> {
+    let mut wrapped = Vec::new();
+    for line in lines {
+        wrapped.extend(wrap(&encode_text(line)?));
+    }
+    let pages: Vec<&[Vec<u8>]> = if wrapped.is_empty() {
+        vec![&[]]
+    } else {
+        wrapped.chunks(LINES_PER_PAGE).collect()
+    };
+
+    // Object ids: 1 catalog, 2 page tree, 3 font, then a (page, content) pair per page
+    let page_id = |i: usize| 4 + 2 * i;
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+
+    objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", page_id(i)))
+        .collect();
+    objects.push(
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+    );
+    objects.push(
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_vec(),
+    );
+
+    for (i, page_lines) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                 /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                page_id(i) + 1
+            )
+            .into_bytes(),
+        );
+
+        let mut stream = format!(
+            "BT /F1 {} Tf {} TL {} {} Td\n",
+            FONT_SIZE,
+            LINE_HEIGHT,
+            MARGIN,
+            PAGE_HEIGHT - MARGIN
+        )
+        .into_bytes();
+        for line in page_lines.iter() {
+            stream.push(b'(');
+            stream.extend(escape(line));
+            stream.extend_from_slice(b") Tj T*\n");
+        }
+        stream.extend_from_slice(b"ET");
+
+        let mut content = format!("<< /Length {} >>\nstream\n", stream.len()).into_bytes();
+        content.extend(stream);
+        content.extend_from_slice(b"\nendstream");
+        objects.push(content);
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, body) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+        pdf.extend(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref_offset = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    pdf.extend(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref_offset
+        )
+        .into_bytes(),
+    );
+
+    Ok(pdf)
+}
+
+/// Replaces the glyphs our digest uses with text Helvetica can draw.
+pub fn pdf_safe(text: &str) -> String {
+    text.replace('★', "*")
+        .replace('½', " 1/2")
+        .replace(['—', '–'], "-")
+        .replace('•', "-")
+}
+
+/// The line as WinAnsi bytes. Letters from other scripts are transliterated
+/// ("Сталкер" becomes "Stalker"); emoji and other symbols the font lacks are
+/// dropped.
+fn encode_text(line: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(line.len());
+    for c in line.chars() {
+        if c.is_control() {
+            continue;
+        }
+        if let Some(byte) = win_ansi(c) {
+            out.push(byte);
+            continue;
+        }
+        if !c.is_alphanumeric() {
+            continue;
+        }
+        match deunicode::deunicode_char(c) {
+            Some(latin)
+                if !latin.is_empty()
+                    && latin.bytes().all(|b| b.is_ascii_graphic() || b == b' ') =>
+            {
+                out.extend_from_slice(latin.as_bytes())
+            }
+            _ => bail!("The PDF font can't draw '{}' in \"{}\"", c, line),
+        }
+    }
+    Ok(out)
+}
+
+/// Where `c` sits in WinAnsiEncoding: Latin-1 plus the typographic
+/// punctuation Windows-1252 keeps in 0x80-0x9F.
+fn win_ansi(c: char) -> Option<u8> {
+    let byte = match c {
+        ' '..='~' | '\u{a0}'..='ÿ' => c as u32 as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        'ƒ' => 0x83,
+        '„' => 0x84,
+        '…' => 0x85,
+        '†' => 0x86,
+        '‡' => 0x87,
+        'ˆ' => 0x88,
+        '‰' => 0x89,
+        'Š' => 0x8a,
+        '‹' => 0x8b,
+        'Œ' => 0x8c,
+        'Ž' => 0x8e,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '˜' => 0x98,
+        '™' => 0x99,
+        'š' => 0x9a,
+        '›' => 0x9b,
+        'œ' => 0x9c,
+        'ž' => 0x9e,
+        'Ÿ' => 0x9f,
+        _ => return None,
+    };
+    Some(byte)
+}
+
+/// Helvetica's advance width for a WinAnsi byte, in thousandths of the font
+/// size. Printable ASCII is exact; everything else gets a wide letter's
+/// width so a wrapped line never runs off the page.
+fn glyph_width(byte: u8) -> u32 {
+    const ASCII: [u16; 95] = [
+        278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278,
+        278, // ' '..'/'
+        556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584,
+        556, // '0'..'?'
+        1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722,
+        778, // '@'..'O'
+        667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469,
+        556, // 'P'..'_'
+        333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556,
+        556, // '`'..'o'
+        556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // 'p'..'~'
+    ];
+    match byte {
+        b' '..=b'~' => ASCII[(byte - b' ') as usize] as u32,
+        _ => 778,
+    }
+}
+
+/// Splits an encoded line into lines that fit the page, breaking at the last
+/// space that fits, or mid-word when a single word is wider than the page.
+fn wrap(line: &[u8]) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    let mut rest = line;
+    let mut indent: &[u8] = &[];
+    loop {
+        let mut width: u32 = indent.iter().map(|&b| glyph_width(b)).sum();
+        let mut fits = rest.len();
+        for (i, &byte) in rest.iter().enumerate() {
+            width += glyph_width(byte);
+            if width > LINE_WIDTH {
+                fits = i;
+                break;
+            }
+        }
+        if fits == rest.len() {
+            lines.push([indent, rest].concat());
+            return lines;
+        }
+
+        let (take, skip) = match rest[..=fits].iter().rposition(|&b| b == b' ') {
+            Some(space) if space > 0 => (space, space + 1),
+            _ => (fits.max(1), fits.max(1)),
+        };
+        lines.push([indent, &rest[..take]].concat());
+        rest = &rest[skip..];
+        indent = CONTINUATION_INDENT;
+    }
+}
+
+/// Escapes encoded text for a PDF string literal.
+fn escape(line: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(line.len());
+    for &byte in line {
+        if matches!(byte, b'(' | b')' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(byte);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width(line: &[u8]) -> u32 {
+        line.iter().map(|&b| glyph_width(b)).sum()
+    }
+
+    #[test]
+    fn accents_and_typographic_punctuation_keep_their_glyphs() {
+        assert_eq!(
+            encode_text("Amélie — “Le Fabuleux”").unwrap(),
+            b"Am\xe9lie \x97 \x93Le Fabuleux\x94"
+        );
+    }
+
+    #[test]
+    fn other_scripts_are_transliterated_and_emoji_dropped() {
+        assert_eq!(encode_text("Сталкер 🍿").unwrap(), b"Stalker ");
+        assert!(
+            encode_text("千と千尋の神隠し")
+                .unwrap()
+                .iter()
+                .all(u8::is_ascii)
+        );
+    }
+
+    #[test]
+    fn long_lines_wrap_at_spaces_within_the_page() {
+        let line = encode_text("Once Upon a Time in Hollywood ".repeat(8).trim_end()).unwrap();
+        let lines = wrap(&line);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| width(line) <= LINE_WIDTH));
+        assert!(lines[1].starts_with(CONTINUATION_INDENT));
+        assert!(lines.iter().all(|line| !line.ends_with(b" ")));
+    }
+
+    #[test]
+    fn a_word_wider_than_the_page_is_broken() {
+        let lines = wrap(&[b'W'; 200]);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| width(line) <= LINE_WIDTH));
+        let letters: usize = lines.iter().flatten().filter(|&&b| b == b'W').count();
+        assert_eq!(letters, 200);
+    }
+
+    #[test]
+    fn a_digest_renders_to_a_pdf() {
+        let lines = vec![
+            "Weekly Movie Roundup".to_string(),
+            "Heat (1995)".to_string(),
+        ];
+        let pdf = render_lines(&lines).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+    }
+}
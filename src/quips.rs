@@ -0,0 +1,124 @@
+use anyhow::{Result, bail};
+
+/// One-liners shown under a film, picked by its group average.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuipPools {
+    /// A perfect 5 average
+    pub perfect: Vec<String>,
+    /// 4 to 4.9
+    pub great: Vec<String>,
+    /// 3 to 3.9
+    pub good: Vec<String>,
+    /// 2 to 2.9
+    pub okay: Vec<String>,
+    /// Below 2
+    pub bad: Vec<String>,
+}
+
+impl Default for QuipPools {
+    fn default() -> Self {
+        let pool = |quips: &[&str]| quips.iter().map(|q| q.to_string()).collect();
+        QuipPools {
+            perfect: pool(&[
+                "🍿 Instant classic.",
+                "🏆 Unanimous acclaim.",
+                "✨ Cinema at its finest.",
+            ]),
+            great: pool(&[
+                "🔥 The group approves.",
+                "👏 Well worth your evening.",
+                "🎯 Hits the spot.",
+            ]),
+            good: pool(&[
+                "🙂 Solid pick.",
+                "👍 Worth a watch.",
+                "🎟️ A decent night in.",
+            ]),
+            okay: pool(&[
+                "😐 Mixed feelings.",
+                "🤷 Your mileage may vary.",
+                "🥱 Background viewing.",
+            ]),
+            bad: pool(&[
+                "💀 Skip it.",
+                "🚫 Consider yourself warned.",
+                "🗑️ The group has spoken.",
+            ]),
+        }
+    }
+}
+
+impl QuipPools {
+    pub fn for_score(&self, score: f32) -> &[String] {
+        if score == 5.0 {
+            &self.perfect
+        } else if score >= 4.0 {
+            &self.great
+        } else if score >= 3.0 {
+            &self.good
+        } else if score >= 2.0 {
+            &self.okay
+        } else {
+            &self.bad
+        }
+    }
+
+    /// Applies a QUIP_MAP such as "bad=💀 Skip it.|Oof,perfect=🏆 Peak." on top
+    /// of these pools. Each mentioned band's pool is replaced by its `|`-separated
+    /// phrases; other bands keep theirs.
+    pub fn with_overrides(mut self, map: &str) -> Result<Self> {
+        for pair in map.split(',').filter(|p| !p.trim().is_empty()) {
+            let Some((band, quips)) = pair.split_once('=') else {
+                bail!("QUIP_MAP entry '{}' should look like band=quip|quip", pair);
+            };
+            let slot = match band.trim().to_lowercase().as_str() {
+                "perfect" => &mut self.perfect,
+                "great" => &mut self.great,
+                "good" => &mut self.good,
+                "okay" => &mut self.okay,
+                "bad" => &mut self.bad,
+                other => bail!(
+                    "Unknown QUIP_MAP band '{}', expected perfect, great, good, okay or bad",
+                    other
+                ),
+            };
+            *slot = quips
+                .split('|')
+                .map(str::trim)
+                .filter(|q| !q.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_fall_into_their_bands() {
+        let pools = QuipPools::default();
+        assert_eq!(pools.for_score(5.0), pools.perfect);
+        assert_eq!(pools.for_score(4.9), pools.great);
+        assert_eq!(pools.for_score(4.0), pools.great);
+        assert_eq!(pools.for_score(3.0), pools.good);
+        assert_eq!(pools.for_score(2.5), pools.okay);
+        assert_eq!(pools.for_score(1.9), pools.bad);
+        assert_eq!(pools.for_score(0.5), pools.bad);
+    }
+
+    #[test]
+    fn overrides_replace_only_the_bands_they_name() {
+        let pools = QuipPools::default()
+            .with_overrides("bad=💀 Oof.| Nope. ,PERFECT=🏆 Peak.")
+            .unwrap();
+        assert_eq!(pools.bad, ["💀 Oof.", "Nope."]);
+        assert_eq!(pools.perfect, ["🏆 Peak."]);
+        assert_eq!(pools.good, QuipPools::default().good);
+
+        assert!(QuipPools::default().with_overrides("awful=Meh").is_err());
+        assert!(QuipPools::default().with_overrides("bad").is_err());
+    }
+}
@@ -0,0 +1,410 @@
+// Month- and year-to-date recaps: per-friend totals and the period's
+// standout films. Feeds only carry a friend's latest 50 or so entries, so
+// the logs earlier digests kept in the state file fill in the rest, and the
+// message says which dates it covers.
+
+use crate::markup::Markup;
+use crate::{MovieGroup, calculate_score, local_midnight};
+use anyhow::{Result, bail};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// How much `--recap` looks back over.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecapPeriod {
+    /// Since the first of this month.
+    Monthly,
+    /// Since the first of January.
+    Yearly,
+}
+
+impl FromStr for RecapPeriod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "monthly" => Ok(RecapPeriod::Monthly),
+            "yearly" => Ok(RecapPeriod::Yearly),
+            other => bail!(
+                "Unknown recap period '{}', expected monthly or yearly",
+                other
+            ),
+        }
+    }
+}
+
+impl RecapPeriod {
+    /// Midnight in `tz` on the first day of the month or year `now` is in.
+    pub fn start(self, now: DateTime<Utc>, tz: Tz) -> DateTime<Utc> {
+        let today = now.with_timezone(&tz).date_naive();
+        let first = match self {
+            RecapPeriod::Monthly => today.with_day(1),
+            RecapPeriod::Yearly => NaiveDate::from_ymd_opt(today.year(), 1, 1),
+        }
+        .unwrap_or(today);
+        local_midnight(first, tz)
+    }
+
+    fn title(self, today: NaiveDate) -> String {
+        match self {
+            RecapPeriod::Monthly => format!("📊 {} Recap 📊", today.format("%B %Y")),
+            RecapPeriod::Yearly => format!("📊 {} Recap 📊", today.year()),
+        }
+    }
+
+    fn noun(self) -> &'static str {
+        match self {
+            RecapPeriod::Monthly => "month",
+            RecapPeriod::Yearly => "year",
+        }
+    }
+}
+
+/// One friend's logs over the period.
+struct FriendTotals {
+    name: String,
+    logs: u32,
+    score_sum: f32,
+    ratings: u32,
+}
+
+/// A film with at least one rating, and how the group rated it.
+struct RatedFilm<'a> {
+    title: &'a str,
+    average: f32,
+    ratings: usize,
+    // Highest and lowest rating, as (friend name, raw stars, score)
+    high: (&'a str, &'a str, f32),
+    low: (&'a str, &'a str, f32),
+}
+
+/// The whole recap message for the films logged since the period started.
+/// `feeds_only` says there were no kept logs to add, so only what the feeds
+/// still list was counted.
+pub fn message(
+    period: RecapPeriod,
+    today: NaiveDate,
+    movies: &HashMap<String, MovieGroup>,
+    feeds_only: bool,
+    markup: &dyn Markup,
+) -> String {
+    let mut message = format!("{}\n\n", markup.heading(&period.title(today)));
+    if movies.is_empty() {
+        message.push_str(&format!("No movies watched this {} 😱", period.noun()));
+        return message;
+    }
+
+    message.push_str(&format!("{}\n", markup.bold("Films logged")));
+    for friend in friend_totals(movies) {
+        let average = match friend.ratings {
+            0 => "no ratings".to_string(),
+            n => format!("avg {:.1}★", friend.score_sum / n as f32),
+        };
+        message.push_str(&format!(
+            "{}{}: {} ({})\n",
+            markup.bullet(),
+            friend.name,
+            friend.logs,
+            average
+        ));
+    }
+    message.push('\n');
+
+    let rated = rated_films(movies);
+    // Best first; more ratings win a tie, then the title keeps it stable
+    let by_average = |a: &&RatedFilm, b: &&RatedFilm| {
+        b.average
+            .total_cmp(&a.average)
+            .then(b.ratings.cmp(&a.ratings))
+            .then(a.title.cmp(b.title))
+    };
+    let highest = rated.iter().min_by(by_average);
+    let lowest = rated.iter().max_by(by_average);
+    if let Some(film) = highest {
+        message.push_str(&format!(
+            "🏆 Highest rated: {} ({:.1}★ from {})\n",
+            markup.bold(film.title),
+            film.average,
+            film.ratings
+        ));
+    }
+    if let Some(film) = lowest
+        && rated.len() > 1
+    {
+        message.push_str(&format!(
+            "👎 Lowest rated: {} ({:.1}★ from {})\n",
+            markup.bold(film.title),
+            film.average,
+            film.ratings
+        ));
+    }
+
+    let most_watched = movies
+        .values()
+        .filter(|group| group.reviews.len() > 1)
+        .max_by(|a, b| {
+            a.reviews
+                .len()
+                .cmp(&b.reviews.len())
+                .then(b.title.cmp(&a.title))
+        });
+    if let Some(group) = most_watched {
+        message.push_str(&format!(
+            "👀 Most watched: {} ({} logs)\n",
+            markup.bold(&group.title),
+            group.reviews.len()
+        ));
+    }
+
+    let disagreement = rated
+        .iter()
+        .filter(|film| film.high.2 > film.low.2)
+        .max_by(|a, b| {
+            (a.high.2 - a.low.2)
+                .total_cmp(&(b.high.2 - b.low.2))
+                .then(b.title.cmp(a.title))
+        });
+    if let Some(film) = disagreement {
+        message.push_str(&format!(
+            "⚔️ Biggest disagreement: {} ({} {} vs {} {})\n",
+            markup.bold(film.title),
+            film.high.0,
+            film.high.1,
+            film.low.0,
+            film.low.1
+        ));
+    }
+
+    format!(
+        "{}\n\n{}",
+        message.trim_end(),
+        markup.italic(&coverage(movies, today, feeds_only))
+    )
+}
+
+/// "Based on 23 logs from 3 Oct to 14 Oct", so a recap that couldn't see
+/// the whole period says so.
+fn coverage(movies: &HashMap<String, MovieGroup>, today: NaiveDate, feeds_only: bool) -> String {
+    let reviews: Vec<_> = movies.values().flat_map(|group| &group.reviews).collect();
+    let earliest = reviews
+        .iter()
+        .filter_map(|review| review.watched_date)
+        .min()
+        .unwrap_or(today);
+    let plural = if reviews.len() == 1 { "log" } else { "logs" };
+    let mut line = format!(
+        "Based on {} {} from {} to {}",
+        reviews.len(),
+        plural,
+        earliest.format("%-d %b"),
+        today.format("%-d %b")
+    );
+    if feeds_only {
+        line.push_str(", only what each feed still lists");
+    }
+    line
+}
+
+/// Logs and ratings per friend, busiest first.
+fn friend_totals(movies: &HashMap<String, MovieGroup>) -> Vec<FriendTotals> {
+    let mut totals: HashMap<String, FriendTotals> = HashMap::new();
+    for review in movies.values().flat_map(|group| &group.reviews) {
+        let friend = totals
+            .entry(review.username.to_lowercase())
+            .or_insert_with(|| FriendTotals {
+                name: review.friend_name.clone(),
+                logs: 0,
+                score_sum: 0.0,
+                ratings: 0,
+            });
+        friend.logs += 1;
+        if !review.rating_raw.is_empty() {
+            friend.score_sum += calculate_score(&review.rating_raw);
+            friend.ratings += 1;
+        }
+    }
+
+    let mut totals: Vec<FriendTotals> = totals.into_values().collect();
+    totals.sort_by(|a, b| b.logs.cmp(&a.logs).then(a.name.cmp(&b.name)));
+    totals
+}
+
+fn rated_films(movies: &HashMap<String, MovieGroup>) -> Vec<RatedFilm<'_>> {
+    let mut films = Vec::new();
+    for group in movies.values() {
+        let ratings: Vec<(&str, &str, f32)> = group
+            .reviews
+            .iter()
+            .filter(|review| !review.rating_raw.is_empty())
+            .map(|review| {
+                (
+                    review.friend_name.as_str(),
+                    review.rating_raw.as_str(),
+                    calculate_score(&review.rating_raw),
+                )
+            })
+            .collect();
+        let (Some(&high), Some(&low)) = (
+            ratings.iter().max_by(|a, b| a.2.total_cmp(&b.2)),
+            ratings.iter().min_by(|a, b| a.2.total_cmp(&b.2)),
+        ) else {
+            continue;
+        };
+        films.push(RatedFilm {
+            title: &group.title,
+            average: ratings.iter().map(|r| r.2).sum::<f32>() / ratings.len() as f32,
+            ratings: ratings.len(),
+            high,
+            low,
+        });
+    }
+    films
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReviewEntry;
+    use crate::markup::WhatsApp;
+    use chrono::TimeZone;
+
+    fn log(name: &str, rating: &str, day: u32) -> ReviewEntry {
+        ReviewEntry {
+            friend_name: name.to_string(),
+            username: name.to_lowercase(),
+            rating_raw: rating.to_string(),
+            watched_date: NaiveDate::from_ymd_opt(2026, 10, day),
+            ..Default::default()
+        }
+    }
+
+    fn films(groups: &[(&str, Vec<ReviewEntry>)]) -> HashMap<String, MovieGroup> {
+        groups
+            .iter()
+            .map(|(title, reviews)| {
+                let group = MovieGroup {
+                    title: title.to_string(),
+                    reviews: reviews.clone(),
+                    ..Default::default()
+                };
+                (title.to_lowercase(), group)
+            })
+            .collect()
+    }
+
+    /// Three friends over the first fortnight of October.
+    fn october() -> HashMap<String, MovieGroup> {
+        films(&[
+            (
+                "Heat",
+                vec![
+                    log("Ann", "★★★★★", 3),
+                    log("Bob", "★★", 5),
+                    log("Cat", "", 6),
+                ],
+            ),
+            ("Up", vec![log("Ann", "★★★★", 8)]),
+            ("Jaws", vec![log("Bob", "★★★", 10), log("Ann", "★★★", 12)]),
+        ])
+    }
+
+    #[test]
+    fn periods_start_at_local_midnight_on_the_first() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 14, 18, 0, 0).unwrap();
+        let london = chrono_tz::Europe::London;
+        // October starts in summer time, January in GMT
+        assert_eq!(
+            RecapPeriod::Monthly.start(now, london),
+            Utc.with_ymd_and_hms(2026, 9, 30, 23, 0, 0).unwrap()
+        );
+        assert_eq!(
+            RecapPeriod::Yearly.start(now, london),
+            Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap()
+        );
+        // Late on the 31st in UTC is already the 1st in Auckland
+        let auckland = chrono_tz::Pacific::Auckland;
+        let halloween = Utc.with_ymd_and_hms(2026, 10, 31, 20, 0, 0).unwrap();
+        assert_eq!(
+            RecapPeriod::Monthly.start(halloween, auckland),
+            Utc.with_ymd_and_hms(2026, 10, 31, 11, 0, 0).unwrap()
+        );
+
+        assert_eq!(
+            "YEARLY".parse::<RecapPeriod>().unwrap(),
+            RecapPeriod::Yearly
+        );
+        assert!("weekly".parse::<RecapPeriod>().is_err());
+    }
+
+    #[test]
+    fn friend_totals_count_logs_and_average_only_ratings() {
+        let mut movies = october();
+        // The same person under a differently cased username is one friend
+        movies.get_mut("up").unwrap().reviews.push(ReviewEntry {
+            username: "ANN".to_string(),
+            ..log("Ann", "★★", 13)
+        });
+
+        let totals: Vec<(String, u32, u32, f32)> = friend_totals(&movies)
+            .into_iter()
+            .map(|f| (f.name, f.logs, f.ratings, f.score_sum))
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                ("Ann".to_string(), 4, 4, 14.0),
+                ("Bob".to_string(), 2, 2, 5.0),
+                ("Cat".to_string(), 1, 0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_recap_sums_up_the_period() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+        let message = message(RecapPeriod::Monthly, today, &october(), false, &WhatsApp);
+        assert_eq!(
+            message,
+            "*📊 October 2026 Recap 📊*\n\n\
+             *Films logged*\n\
+             • Ann: 3 (avg 4.0★)\n\
+             • Bob: 2 (avg 2.5★)\n\
+             • Cat: 1 (no ratings)\n\n\
+             🏆 Highest rated: *Up* (4.0★ from 1)\n\
+             👎 Lowest rated: *Jaws* (3.0★ from 2)\n\
+             👀 Most watched: *Heat* (3 logs)\n\
+             ⚔️ Biggest disagreement: *Heat* (Ann ★★★★★ vs Bob ★★)\n\n\
+             _Based on 6 logs from 3 Oct to 14 Oct_"
+        );
+
+        let feeds_only = message_for_feeds_only(today);
+        assert!(
+            feeds_only.ends_with(
+                "_Based on 1 log from 8 Oct to 14 Oct, only what each feed still lists_"
+            )
+        );
+        assert!(!feeds_only.contains("Lowest rated"));
+    }
+
+    fn message_for_feeds_only(today: NaiveDate) -> String {
+        let movies = films(&[("Up", vec![log("Ann", "★★★★", 8)])]);
+        message(RecapPeriod::Yearly, today, &movies, true, &WhatsApp)
+    }
+
+    #[test]
+    fn an_empty_period_says_so() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 14).unwrap();
+        assert_eq!(
+            message(
+                RecapPeriod::Yearly,
+                today,
+                &HashMap::new(),
+                false,
+                &WhatsApp
+            ),
+            "*📊 2026 Recap 📊*\n\nNo movies watched this year 😱"
+        );
+    }
+}
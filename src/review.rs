@@ -0,0 +1,74 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Longest snippet shown under a reviewer line before it gets cut with "…"
+const MAX_SNIPPET_CHARS: usize = 140;
+
+const SPOILER_TITLE_SUFFIX: &str = "(contains spoilers)";
+const SPOILER_NOTICE: &str = "This review may contain spoilers.";
+
+static PARAGRAPH_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<p>(.*?)</p>").expect("valid paragraph regex"));
+static TAG_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<[^>]+>").expect("valid tag regex"));
+
+/// What we could pull out of a diary entry's HTML description.
+pub struct ReviewText {
+    pub snippet: Option<String>,
+    pub spoiler: bool,
+}
+
+/// Letterboxd appends "(contains spoilers)" to the item title of flagged
+/// reviews. Returns the title without it, and whether it was there.
+pub fn strip_spoiler_suffix(raw_title: &str) -> (&str, bool) {
+    match raw_title.trim_end().strip_suffix(SPOILER_TITLE_SUFFIX) {
+        Some(title) => (title.trim_end(), true),
+        None => (raw_title, false),
+    }
+}
+
+/// The description is `<p><img poster/></p>` followed by either the review
+/// paragraphs or a "Watched on ..." line for entries without a review.
+pub fn parse_description(html: &str) -> ReviewText {
+    let mut spoiler = false;
+    let mut paragraphs = Vec::new();
+
+    for caps in PARAGRAPH_REGEX.captures_iter(html) {
+        let text = decode_entities(TAG_REGEX.replace_all(&caps[1], "").trim());
+        if text.is_empty() || text.starts_with("Watched on ") {
+            continue;
+        }
+        if text == SPOILER_NOTICE {
+            spoiler = true;
+            continue;
+        }
+        paragraphs.push(text);
+    }
+
+    let snippet = if paragraphs.is_empty() {
+        None
+    } else {
+        Some(truncate_chars(&paragraphs.join(" "), MAX_SNIPPET_CHARS))
+    };
+
+    ReviewText { snippet, spoiler }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#039;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Cuts on a char boundary so multi-byte text never panics.
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(max).collect();
+    format!("{}…", cut.trim_end())
+}
@@ -0,0 +1,115 @@
+// Reading the friend sheet: the CSV is parsed as it streams in, and friends
+// listed in more than one circle are folded into one.
+
+use anyhow::{Context, Result};
+use futures_util::StreamExt;
+use std::collections::HashSet;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead};
+use tracing::{debug, info};
+
+pub const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// One row of the friend sheet.
+pub struct Friend {
+    pub name: String,
+    pub username: String,
+    // Optional third column, e.g. a flag shown before the name
+    pub flair: Option<String>,
+}
+
+/// Sheets exports can start with a BOM, which would end up in the first header.
+pub async fn without_bom<R: AsyncBufRead + Unpin>(mut reader: R) -> std::io::Result<R> {
+    if reader.fill_buf().await?.starts_with(UTF8_BOM) {
+        reader.consume(UTF8_BOM.len());
+    }
+    Ok(reader)
+}
+
+pub async fn parse_sheet<R: AsyncRead + Unpin + Send>(body: R) -> Result<Vec<Friend>> {
+    let mut rdr = csv_async::AsyncReader::from_reader(body);
+
+    // Catch a sheet pointed at the wrong CSV before it fails deep in the loop
+    let columns = rdr
+        .headers()
+        .await?
+        .iter()
+        .filter(|h| !h.trim().is_empty())
+        .count();
+    if columns < 2 {
+        anyhow::bail!(
+            "CSV needs at least name and username columns; found {}",
+            columns
+        );
+    }
+
+    let mut friends = Vec::new();
+    let mut records = rdr.records();
+    while let Some(result) = records.next().await {
+        let record = result?;
+        let name = record.get(0).unwrap_or("Unknown").trim();
+        let username = record.get(1).context("No username")?.trim();
+
+        // A leading '#' parks a friend in the sheet without fetching them
+        if name.starts_with('#') || username.starts_with('#') {
+            info!(name, username, "Skipping commented-out row");
+            continue;
+        }
+
+        friends.push(Friend {
+            name: name.to_string(),
+            username: username.to_string(),
+            flair: record
+                .get(2)
+                .map(str::trim)
+                .filter(|flair| !flair.is_empty())
+                .map(str::to_string),
+        });
+    }
+    Ok(friends)
+}
+
+/// Everyone across the sheets, in order. Someone listed in more than one
+/// circle (same name, ignoring case and spacing) is only kept the first time.
+pub fn unique_friends(sheets: impl IntoIterator<Item = Friend>) -> Vec<Friend> {
+    let mut seen_names: HashSet<String> = HashSet::new();
+    let mut friends = Vec::new();
+    for friend in sheets {
+        let normalized = friend
+            .name
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if seen_names.insert(normalized) {
+            friends.push(friend);
+        } else {
+            debug!(friend = %friend.name, "In more than one sheet, keeping the first");
+        }
+    }
+    friends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn friend(name: &str, username: &str) -> Friend {
+        Friend {
+            name: name.to_string(),
+            username: username.to_string(),
+            flair: None,
+        }
+    }
+
+    #[test]
+    fn a_friend_in_two_circles_is_kept_once_where_first_listed() {
+        let friends = unique_friends([
+            friend("Ann Lee", "ann"),
+            friend("Bob", "bob"),
+            friend("  ann   LEE ", "ann_alt"),
+            friend("Annie Lee", "annie"),
+        ]);
+        let kept: Vec<&str> = friends.iter().map(|f| f.username.as_str()).collect();
+        assert_eq!(kept, ["ann", "bob", "annie"]);
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// Confirmed sends older than this can't belong to a run being retried
+const SENT_RETENTION_DAYS: i64 = 14;
+// Long enough for a yearly recap run a little after New Year
+const LOG_RETENTION_DAYS: i64 = 400;
+
+/// A message Whapi confirmed, keyed by the idempotency key it was sent under.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SentChunk {
+    pub key: String,
+    pub message_id: String,
+    pub date: NaiveDate,
+}
+
+/// One run's ratings of a film, kept as a sum so weeks can be recombined
+/// into a running average.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WeekScores {
+    pub week: NaiveDate,
+    pub score_sum: f32,
+    pub ratings: u32,
+}
+
+/// The logs counted toward a friend's total for one year.
+#[derive(Serialize, Deserialize, Default)]
+pub struct YearlyLogs {
+    pub year: i32,
+    // Feed guids already counted, so overlapping windows and reruns don't
+    // count a log twice
+    pub seen: Vec<String>,
+}
+
+/// One diary entry a digest included, kept so recaps can look back further
+/// than a feed's latest entries.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LoggedFilm {
+    // The feed item's guid, so a log seen by several runs is kept once
+    pub entry_id: String,
+    pub film_key: String,
+    pub title: String,
+    pub link: String,
+    pub friend_name: String,
+    pub username: String,
+    pub rating_raw: String,
+    // The watched date, or the day of the digest when the entry had none
+    pub date: NaiveDate,
+}
+
+/// Everything the bot remembers between runs, stored as JSON at STATE_PATH.
+#[derive(Serialize, Deserialize, Default)]
+pub struct State {
+    #[serde(default)]
+    pub sent: Vec<SentChunk>,
+    // When a digest run last finished without error; drives /readyz
+    #[serde(default)]
+    pub last_success: Option<DateTime<Utc>>,
+    // Per film key, the ratings each past digest included
+    #[serde(default)]
+    pub film_history: HashMap<String, Vec<WeekScores>>,
+    // Per lowercased username, when the newest entry of the last sent digest
+    // was published; SINCE_LAST_SEND leaves out anything up to it
+    #[serde(default)]
+    pub watermarks: HashMap<String, DateTime<Utc>>,
+    // Per lowercased username, this year's logs for SHOW_GOALS
+    #[serde(default)]
+    pub yearly_logs: HashMap<String, YearlyLogs>,
+    // Every log of the past LOG_RETENTION_DAYS, for --recap
+    #[serde(default)]
+    pub logs: Vec<LoggedFilm>,
+}
+
+impl State {
+    /// A missing file is a fresh start; a corrupt one is an error so we
+    /// don't silently forget what was already posted.
+    pub fn load(path: &str) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .with_context(|| format!("State file {} is not valid JSON", path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(e).with_context(|| format!("Couldn't read state file {}", path)),
+        }
+    }
+
+    /// Writes to a temp file and renames it over the old one, so a crash
+    /// mid-write never leaves half a state file behind.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn sent_message_id(&self, key: &str) -> Option<&str> {
+        self.sent
+            .iter()
+            .find(|chunk| chunk.key == key)
+            .map(|chunk| chunk.message_id.as_str())
+    }
+
+    pub fn record_sent(&mut self, key: &str, message_id: &str, today: NaiveDate) {
+        self.sent
+            .retain(|chunk| today - chunk.date <= Duration::days(SENT_RETENTION_DAYS));
+        self.sent.push(SentChunk {
+            key: key.to_string(),
+            message_id: message_id.to_string(),
+            date: today,
+        });
+    }
+
+    /// Rating sum and count for a film across digests before `today`, or
+    /// `None` when it has never been rated in one.
+    pub fn film_totals_before(&self, key: &str, today: NaiveDate) -> Option<(f32, u32)> {
+        let (sum, ratings) = self
+            .film_history
+            .get(key)?
+            .iter()
+            .filter(|week| week.week < today)
+            .fold((0.0, 0), |(sum, n), week| {
+                (sum + week.score_sum, n + week.ratings)
+            });
+        (ratings > 0).then_some((sum, ratings))
+    }
+
+    /// Records today's ratings of a film, replacing any earlier run today so
+    /// a rerun doesn't count them twice.
+    pub fn record_film_week(&mut self, key: &str, today: NaiveDate, score_sum: f32, ratings: u32) {
+        let weeks = self.film_history.entry(key.to_string()).or_default();
+        weeks.retain(|week| week.week != today);
+        weeks.push(WeekScores {
+            week: today,
+            score_sum,
+            ratings,
+        });
+    }
+
+    /// Counts a log toward its friend's total for `year`, once however many
+    /// runs see it. A new year starts the count over. Usernames are matched
+    /// case-insensitively, like the roster.
+    pub fn count_log(&mut self, username: &str, year: i32, entry_id: &str) {
+        let logs = self.yearly_logs.entry(username.to_lowercase()).or_default();
+        if logs.year != year {
+            *logs = YearlyLogs {
+                year,
+                seen: Vec::new(),
+            };
+        }
+        if !logs.seen.iter().any(|seen| seen == entry_id) {
+            logs.seen.push(entry_id.to_string());
+        }
+    }
+
+    pub fn yearly_count(&self, username: &str, year: i32) -> u32 {
+        self.yearly_logs
+            .get(&username.to_lowercase())
+            .filter(|logs| logs.year == year)
+            .map_or(0, |logs| logs.seen.len() as u32)
+    }
+
+    /// Adds logs not already kept and forgets those past the retention.
+    pub fn record_logs(&mut self, logs: &[LoggedFilm], today: NaiveDate) {
+        self.logs
+            .retain(|log| today - log.date <= Duration::days(LOG_RETENTION_DAYS));
+        for log in logs {
+            if !self.logs.iter().any(|kept| kept.entry_id == log.entry_id) {
+                self.logs.push(log.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_log_counts_once_however_many_runs_see_it() {
+        let mut state = State::default();
+        state.count_log("ann", 2026, "entry-1");
+        state.count_log("ann", 2026, "entry-2");
+        state.count_log("ann", 2026, "entry-1");
+        assert_eq!(state.yearly_count("ann", 2026), 2);
+        assert_eq!(state.yearly_count("bob", 2026), 0);
+    }
+
+    #[test]
+    fn usernames_count_case_insensitively() {
+        let mut state = State::default();
+        state.count_log("Ann", 2026, "entry-1");
+        state.count_log("ann", 2026, "entry-2");
+        assert_eq!(state.yearly_count("ANN", 2026), 2);
+        assert_eq!(state.yearly_logs.len(), 1);
+    }
+
+    #[test]
+    fn a_new_year_starts_the_count_over() {
+        let mut state = State::default();
+        state.count_log("ann", 2025, "entry-1");
+        state.count_log("ann", 2025, "entry-2");
+        state.count_log("ann", 2026, "entry-3");
+        assert_eq!(state.yearly_count("ann", 2026), 1);
+        assert_eq!(state.yearly_count("ann", 2025), 0);
+    }
+}
@@ -0,0 +1,230 @@
+// A tall "story" summary card drawn with a built-in 5×7 bitmap font, so
+// there's no font file to ship. The font only has A–Z, digits and a little
+// punctuation: lowercase is drawn as uppercase, common accents are dropped
+// and anything else comes out as '?'.
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, Rgb, RgbImage};
+use std::io::Cursor;
+
+const CARD_WIDTH: u32 = 1080;
+const CARD_HEIGHT: u32 = 1920;
+const MARGIN: u32 = 60;
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+
+// Letterboxd's dark slate, with its green and orange for headings
+const BACKGROUND: Rgb<u8> = Rgb([20, 24, 28]);
+const TITLE_COLOUR: Rgb<u8> = Rgb([0, 224, 84]);
+const SECTION_COLOUR: Rgb<u8> = Rgb([255, 128, 0]);
+const BODY_COLOUR: Rgb<u8> = Rgb([230, 234, 238]);
+
+// Pixels per font dot for each kind of line
+const TITLE_SCALE: u32 = 10;
+const SECTION_SCALE: u32 = 7;
+const BODY_SCALE: u32 = 5;
+
+/// One block of the card: an orange heading and the lines under it.
+pub struct Section {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+/// Draws the title and sections top to bottom and returns PNG bytes.
+/// Lines too wide for the card are cut short, and sections that would run
+/// off the bottom are left out.
+pub fn render(title: &str, sections: &[Section]) -> Result<Vec<u8>> {
+    let mut canvas = RgbImage::from_pixel(CARD_WIDTH, CARD_HEIGHT, BACKGROUND);
+    let mut y = MARGIN;
+    draw_line(&mut canvas, title, y, TITLE_SCALE, TITLE_COLOUR);
+    y += line_height(TITLE_SCALE) * 2;
+
+    for section in sections {
+        let height =
+            line_height(SECTION_SCALE) + section.lines.len() as u32 * line_height(BODY_SCALE);
+        if y + height > CARD_HEIGHT - MARGIN {
+            break;
+        }
+        draw_line(
+            &mut canvas,
+            &section.title,
+            y,
+            SECTION_SCALE,
+            SECTION_COLOUR,
+        );
+        y += line_height(SECTION_SCALE);
+        for line in &section.lines {
+            draw_line(&mut canvas, line, y, BODY_SCALE, BODY_COLOUR);
+            y += line_height(BODY_SCALE);
+        }
+        y += line_height(BODY_SCALE);
+    }
+
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(canvas)
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .context("Couldn't encode the story card")?;
+    Ok(png)
+}
+
+// A glyph plus one dot of spacing across
+fn advance(scale: u32) -> u32 {
+    (GLYPH_WIDTH + 1) * scale
+}
+
+// A glyph plus three dots of leading down
+fn line_height(scale: u32) -> u32 {
+    (GLYPH_HEIGHT + 3) * scale
+}
+
+fn draw_line(canvas: &mut RgbImage, text: &str, y: u32, scale: u32, colour: Rgb<u8>) {
+    let max_chars = ((CARD_WIDTH - 2 * MARGIN) / advance(scale)) as usize;
+    let chars: Vec<char> = text.trim().chars().map(fold_char).collect();
+    let shown: Vec<char> = if chars.len() > max_chars {
+        let mut cut = chars[..max_chars.saturating_sub(3)].to_vec();
+        cut.extend("...".chars());
+        cut
+    } else {
+        chars
+    };
+
+    for (i, c) in shown.into_iter().enumerate() {
+        let x = MARGIN + i as u32 * advance(scale);
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - col)) == 0 {
+                    continue;
+                }
+                let (left, top) = (x + col * scale, y + row as u32 * scale);
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        canvas.put_pixel(left + dx, top + dy, colour);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Maps a character onto one the font can draw.
+fn fold_char(c: char) -> char {
+    match c {
+        'À'..='Å' | 'à'..='å' => 'A',
+        'Ç' | 'ç' => 'C',
+        'È'..='Ë' | 'è'..='ë' => 'E',
+        'Ì'..='Ï' | 'ì'..='ï' => 'I',
+        'Ñ' | 'ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' | 'ò'..='ö' | 'ø' => 'O',
+        'Ù'..='Ü' | 'ù'..='ü' => 'U',
+        'Ý' | 'ý' | 'ÿ' => 'Y',
+        '★' => '*',
+        '½' => '+',
+        '’' | '‘' => '\'',
+        '“' | '”' => '"',
+        '–' | '—' | '·' => '-',
+        c => c.to_ascii_uppercase(),
+    }
+}
+
+/// Rows top to bottom, the low five bits of each, leftmost dot highest.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '\'' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '"' => [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '&' => [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_card_is_a_png_of_story_size() {
+        let sections = [Section {
+            title: "Top rated".to_string(),
+            lines: vec!["4.5 Amélie".to_string(), "3.0 Heat ★★★".to_string()],
+        }];
+        let png = render("Weekly round-up", &sections).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let card = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+            .unwrap()
+            .to_rgb8();
+        assert_eq!(card.dimensions(), (CARD_WIDTH, CARD_HEIGHT));
+        assert_eq!(*card.get_pixel(0, 0), BACKGROUND);
+        assert!(card.pixels().any(|pixel| *pixel == TITLE_COLOUR));
+        assert!(card.pixels().any(|pixel| *pixel == SECTION_COLOUR));
+        assert!(card.pixels().any(|pixel| *pixel == BODY_COLOUR));
+    }
+
+    #[test]
+    fn sections_that_would_run_off_the_bottom_are_left_out() {
+        let tall = Section {
+            title: "Too much".to_string(),
+            lines: vec!["X".to_string(); 100],
+        };
+        let card = image::load_from_memory(&render("Title", &[tall]).unwrap())
+            .unwrap()
+            .to_rgb8();
+        assert!(!card.pixels().any(|pixel| *pixel == SECTION_COLOUR));
+    }
+
+    #[test]
+    fn characters_outside_the_font_are_folded() {
+        assert_eq!(fold_char('é'), 'E');
+        assert_eq!(fold_char('★'), '*');
+        assert_eq!(fold_char('q'), 'Q');
+        assert_eq!(glyph('é'), glyph('?'));
+    }
+}
@@ -0,0 +1,79 @@
+use serde_json::Value;
+
+/// Reaction counts on one message, most popular first.
+pub struct ReactionTally {
+    pub counts: Vec<(String, u32)>,
+}
+
+impl ReactionTally {
+    /// Reads the `reactions` array of a Whapi message object. Entries may
+    /// carry a `count`; without one each entry is a single vote.
+    pub fn from_message(message: &Value) -> Self {
+        let mut counts: Vec<(String, u32)> = Vec::new();
+        let reactions = message
+            .get("reactions")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+
+        for reaction in reactions {
+            let Some(emoji) = reaction.get("emoji").and_then(Value::as_str) else {
+                continue;
+            };
+            let votes = reaction.get("count").and_then(Value::as_u64).unwrap_or(1) as u32;
+            match counts.iter_mut().find(|(e, _)| e == emoji) {
+                Some((_, count)) => *count += votes,
+                None => counts.push((emoji.to_string(), votes)),
+            }
+        }
+
+        // Stable sort keeps the first-reacted emoji ahead on a tie
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        ReactionTally { counts }
+    }
+
+    /// All emoji sharing the top count, so ties can be announced as such.
+    pub fn winners(&self) -> Vec<&(String, u32)> {
+        let top = self.counts.first().map_or(0, |(_, count)| *count);
+        self.counts
+            .iter()
+            .filter(|(_, count)| *count == top && top > 0)
+            .collect()
+    }
+}
+
+/// The "winner" announcement. A poll message lists options as lines starting
+/// with an emoji ("1️⃣ Dune"), so a winning reaction is matched back to its line.
+pub fn results_message(tally: &ReactionTally, poll_text: &str) -> String {
+    let winners = tally.winners();
+    if winners.is_empty() {
+        return "🗳️ No votes on the poll yet!".to_string();
+    }
+
+    let options: Vec<String> = winners
+        .iter()
+        .map(|(emoji, _)| {
+            poll_text
+                .lines()
+                .map(str::trim)
+                .find(|line| line.starts_with(emoji.as_str()))
+                .map_or_else(|| emoji.clone(), str::to_string)
+        })
+        .collect();
+    let votes = winners[0].1;
+    let plural = if votes == 1 { "vote" } else { "votes" };
+
+    if options.len() == 1 {
+        format!(
+            "🏆 *The votes are in!* Winner: {} with {} {}",
+            options[0], votes, plural
+        )
+    } else {
+        format!(
+            "🏆 *It's a tie!* {} with {} {} each",
+            options.join(" & "),
+            votes,
+            plural
+        )
+    }
+}
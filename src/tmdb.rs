@@ -0,0 +1,244 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::warn;
+
+const TMDB_API: &str = "https://api.themoviedb.org/3";
+// Ids and details hardly ever change; streaming providers do, so those
+// are never written to the cache file
+const CACHE_TTL_DAYS: i64 = 30;
+
+/// The parts of TMDB's movie details the digest uses.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MovieDetails {
+    pub genres: Vec<String>,
+    pub runtime_minutes: Option<u32>,
+    pub imdb_id: Option<String>,
+    // e.g. "/abc123.jpg", relative to TMDB's image host
+    pub poster_path: Option<String>,
+    // From the release date, for films whose feed entry had no year
+    #[serde(default)]
+    pub release_year: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cached<T> {
+    value: T,
+    fetched_at: DateTime<Utc>,
+}
+
+/// The lookups kept in TMDB_CACHE_PATH between runs.
+#[derive(Serialize, Deserialize, Default)]
+struct Cache {
+    // Keyed by Letterboxd slug (or title when there's no slug)
+    #[serde(default)]
+    ids: HashMap<String, Cached<Option<u64>>>,
+    #[serde(default)]
+    details: HashMap<u64, Cached<MovieDetails>>,
+}
+
+/// A thin TMDB client that remembers every lookup for the rest of the run,
+/// so the same film reviewed by several friends is only resolved once. With
+/// a cache path, ids and details are remembered across runs too.
+pub struct Tmdb {
+    client: Client,
+    api_key: String,
+    cache_path: Option<String>,
+    now: DateTime<Utc>,
+    cache: Cache,
+    providers: HashMap<(u64, String), Vec<String>>,
+}
+
+impl Tmdb {
+    /// A missing or unreadable cache file just means starting empty.
+    pub fn new(
+        client: Client,
+        api_key: String,
+        cache_path: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let cache = cache_path
+            .as_deref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default();
+        Tmdb {
+            client,
+            api_key,
+            cache_path,
+            now,
+            cache,
+            providers: HashMap::new(),
+        }
+    }
+
+    fn is_fresh(&self, fetched_at: DateTime<Utc>) -> bool {
+        self.now - fetched_at < Duration::days(CACHE_TTL_DAYS)
+    }
+
+    /// Writes the cache back with anything stale dropped. Failing to save
+    /// only costs the next run some lookups, so it's reported, not fatal.
+    pub fn save_cache(&mut self) {
+        let Some(path) = &self.cache_path else {
+            return;
+        };
+        let now = self.now;
+        let fresh = |fetched_at: DateTime<Utc>| now - fetched_at < Duration::days(CACHE_TTL_DAYS);
+        self.cache.ids.retain(|_, entry| fresh(entry.fetched_at));
+        self.cache
+            .details
+            .retain(|_, entry| fresh(entry.fetched_at));
+
+        let saved = serde_json::to_string_pretty(&self.cache)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(std::fs::write(path, json)?));
+        if let Err(e) = saved {
+            warn!(path = %path, error = %e, "Couldn't save the TMDB cache");
+        }
+    }
+
+    async fn get_json(&self, path: &str, query: &[(&str, &str)]) -> Result<Value> {
+        let response = self
+            .client
+            .get(format!("{}{}", TMDB_API, path))
+            .query(&[("api_key", self.api_key.as_str())])
+            .query(query)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("TMDB {} failed with status {}", path, response.status());
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Finds the TMDB movie id for a film, using the year to pick between
+    /// remakes. `Ok(None)` means TMDB had no match.
+    pub async fn find_movie_id(
+        &mut self,
+        cache_key: &str,
+        title: &str,
+        year: Option<i32>,
+    ) -> Result<Option<u64>> {
+        if let Some(cached) = self.cache.ids.get(cache_key)
+            && self.is_fresh(cached.fetched_at)
+        {
+            return Ok(cached.value);
+        }
+
+        let year = year.map(|y| y.to_string());
+        let mut query = vec![("query", title)];
+        if let Some(year) = &year {
+            query.push(("year", year.as_str()));
+        }
+
+        let json = self.get_json("/search/movie", &query).await?;
+        let id = json
+            .get("results")
+            .and_then(|r| r.get(0))
+            .and_then(|m| m.get("id"))
+            .and_then(Value::as_u64);
+
+        self.cache.ids.insert(
+            cache_key.to_string(),
+            Cached {
+                value: id,
+                fetched_at: self.now,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Names of the subscription services streaming the film in `region`.
+    pub async fn watch_providers(&mut self, movie_id: u64, region: &str) -> Result<Vec<String>> {
+        let key = (movie_id, region.to_string());
+        if let Some(names) = self.providers.get(&key) {
+            return Ok(names.clone());
+        }
+
+        let json = self
+            .get_json(&format!("/movie/{}/watch/providers", movie_id), &[])
+            .await?;
+        let names: Vec<String> = json
+            .get("results")
+            .and_then(|r| r.get(region))
+            .and_then(|r| r.get("flatrate"))
+            .and_then(Value::as_array)
+            .map(|providers| {
+                providers
+                    .iter()
+                    .filter_map(|p| p.get("provider_name").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        self.providers.insert(key, names.clone());
+        Ok(names)
+    }
+
+    pub async fn movie_details(&mut self, movie_id: u64) -> Result<MovieDetails> {
+        if let Some(cached) = self.cache.details.get(&movie_id)
+            && self.is_fresh(cached.fetched_at)
+        {
+            return Ok(cached.value.clone());
+        }
+
+        let json = self.get_json(&format!("/movie/{}", movie_id), &[]).await?;
+        let genres = json
+            .get("genres")
+            .and_then(Value::as_array)
+            .map(|genres| {
+                genres
+                    .iter()
+                    .filter_map(|g| g.get("name").and_then(Value::as_str))
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // TMDB reports 0 for films it has no runtime for
+        let runtime_minutes = json
+            .get("runtime")
+            .and_then(Value::as_u64)
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| minutes as u32);
+
+        let imdb_id = json
+            .get("imdb_id")
+            .and_then(Value::as_str)
+            .filter(|id| !id.is_empty())
+            .map(str::to_string);
+
+        let poster_path = json
+            .get("poster_path")
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        // "1999-03-30", or empty for unreleased films
+        let release_year = json
+            .get("release_date")
+            .and_then(Value::as_str)
+            .and_then(|date| date.get(..4))
+            .and_then(|year| year.parse().ok());
+
+        let details = MovieDetails {
+            genres,
+            runtime_minutes,
+            imdb_id,
+            poster_path,
+            release_year,
+        };
+        self.cache.details.insert(
+            movie_id,
+            Cached {
+                value: details.clone(),
+                fetched_at: self.now,
+            },
+        );
+        Ok(details)
+    }
+}
@@ -0,0 +1,91 @@
+use crate::config::{Config, MessengerKind};
+use crate::{FeedError, feed_url, fetch_and_parse_feed, fetch_friends, letterboxd_pacer};
+use anyhow::Result;
+use reqwest::{Client, StatusCode};
+
+/// The pre-flight check behind `--validate`: the sheet, every friend's feed
+/// and, with MESSENGER=whapi, the Whapi token, reported one line each.
+/// Nothing is sent to the group.
+pub async fn run(client: &Client, config: &Config) -> Result<()> {
+    let mut problems = 0;
+
+    let friends = match fetch_friends(client, config).await {
+        Ok(friends) => {
+            println!("✔ Friend sheet: {} friends", friends.len());
+            friends
+        }
+        Err(e) => {
+            println!("✘ Friend sheet: {:#}", e);
+            problems += 1;
+            Vec::new()
+        }
+    };
+
+    let pacer = letterboxd_pacer(config);
+    for friend in &friends {
+        pacer.wait().await;
+        let url = feed_url(&config.letterboxd_base, &friend.username);
+        let timeout = std::time::Duration::from_secs(config.feed_timeout_secs);
+        match fetch_and_parse_feed(client, &url, config.max_feed_bytes, timeout).await {
+            Ok(channel) => println!(
+                "✔ {} ({}): {} entries",
+                friend.name,
+                friend.username,
+                channel.items().len()
+            ),
+            Err(reason) => {
+                println!(
+                    "✘ {} ({}): {}",
+                    friend.name,
+                    friend.username,
+                    describe_feed_error(&reason)
+                );
+                problems += 1;
+            }
+        }
+    }
+
+    if config.messenger == MessengerKind::Whapi {
+        match check_whapi_token(client, &config.whapi_token).await {
+            Ok(()) => println!("✔ Whapi token accepted"),
+            Err(e) => {
+                println!("✘ Whapi token: {:#}", e);
+                problems += 1;
+            }
+        }
+    } else {
+        println!("- {:?} credentials aren't checked", config.messenger);
+    }
+
+    if problems > 0 {
+        anyhow::bail!("{} check(s) failed", problems);
+    }
+    println!("All checks passed");
+    Ok(())
+}
+
+/// Letterboxd answers 404 both for a mistyped username and a private profile.
+fn describe_feed_error(reason: &FeedError) -> String {
+    match reason {
+        FeedError::Status(StatusCode::NOT_FOUND) => {
+            "not found (check the username, or the profile may be private)".to_string()
+        }
+        other => other.to_string(),
+    }
+}
+
+/// An authorized call that changes nothing, to catch an expired token.
+async fn check_whapi_token(client: &Client, token: &str) -> Result<()> {
+    let response = client
+        .get("https://gate.whapi.cloud/health")
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_body = response.text().await.unwrap_or_default();
+        anyhow::bail!("Status: {}. Details: {}", status, error_body);
+    }
+    Ok(())
+}